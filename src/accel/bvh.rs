@@ -0,0 +1,310 @@
+use std::f64;
+
+use crate::math::aabb::Aabb;
+use crate::math::point3::Point3;
+use crate::math::ray::Ray;
+use crate::primitives::Intersection;
+
+const MAX_LEAF_SIZE: usize = 4;
+const SAH_BUCKETS: usize = 12;
+const TRAVERSAL_COST: f64 = 0.125; // relative to one primitive intersection
+
+#[derive(Debug)]
+enum BvhNode {
+    Leaf {
+        bounds: Aabb,
+        start: usize,
+        count: usize,
+    },
+    // the left child always directly follows its parent
+    Interior {
+        bounds: Aabb,
+        right: usize,
+        axis: usize,
+    },
+}
+
+impl BvhNode {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            BvhNode::Leaf { bounds, .. } => bounds,
+            BvhNode::Interior { bounds, .. } => bounds,
+        }
+    }
+}
+
+// Bounding volume hierarchy over primitive indices, built with the surface area heuristic
+#[derive(Debug)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    indices: Vec<usize>,
+}
+
+struct BuildItem {
+    index: usize,
+    bounds: Aabb,
+    centroid: [f64; 3],
+}
+
+impl Bvh {
+    pub fn build(bounds: &[Aabb]) -> Bvh {
+        let mut items: Vec<BuildItem> = bounds
+            .iter()
+            .enumerate()
+            .map(|(index, b)| {
+                let c = b.centroid();
+                BuildItem {
+                    index,
+                    bounds: *b,
+                    centroid: [*c.x(), *c.y(), *c.z()],
+                }
+            })
+            .collect();
+
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(2 * bounds.len()),
+            indices: Vec::with_capacity(bounds.len()),
+        };
+        if !items.is_empty() {
+            bvh.build_recursive(&mut items);
+        }
+        bvh
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        match self.nodes.first() {
+            Some(node) => *node.bounds(),
+            None => Aabb::empty(),
+        }
+    }
+
+    fn build_recursive(&mut self, items: &mut [BuildItem]) -> usize {
+        let bounds = items
+            .iter()
+            .fold(Aabb::empty(), |acc, item| acc.union(&item.bounds));
+        let node_index = self.nodes.len();
+
+        if items.len() <= MAX_LEAF_SIZE {
+            self.push_leaf(bounds, items);
+            return node_index;
+        }
+
+        let centroid_bounds = items.iter().fold(Aabb::empty(), |acc, item| {
+            acc.union_point(&Point3::new(
+                item.centroid[0],
+                item.centroid[1],
+                item.centroid[2],
+            ))
+        });
+        let axis = centroid_bounds.max_axis();
+        let c_min = centroid_bounds.min.axis(axis);
+        let c_extent = centroid_bounds.max.axis(axis) - c_min;
+
+        if c_extent <= 0. {
+            // every centroid coincides, no split can separate them
+            self.push_leaf(bounds, items);
+            return node_index;
+        }
+
+        let bucket_of = |centroid: f64| {
+            let b = ((centroid - c_min) / c_extent * SAH_BUCKETS as f64) as usize;
+            b.min(SAH_BUCKETS - 1)
+        };
+
+        let mut bucket_counts = [0usize; SAH_BUCKETS];
+        let mut bucket_bounds = [Aabb::empty(); SAH_BUCKETS];
+        for item in items.iter() {
+            let b = bucket_of(item.centroid[axis]);
+            bucket_counts[b] += 1;
+            bucket_bounds[b] = bucket_bounds[b].union(&item.bounds);
+        }
+
+        // cost of splitting after each bucket
+        let mut best_cost = f64::INFINITY;
+        let mut best_split = 0;
+        for split in 0..SAH_BUCKETS - 1 {
+            let (mut left, mut right) = (Aabb::empty(), Aabb::empty());
+            let (mut n_left, mut n_right) = (0, 0);
+            for b in 0..=split {
+                left = left.union(&bucket_bounds[b]);
+                n_left += bucket_counts[b];
+            }
+            for b in split + 1..SAH_BUCKETS {
+                right = right.union(&bucket_bounds[b]);
+                n_right += bucket_counts[b];
+            }
+            let cost = TRAVERSAL_COST
+                + (n_left as f64 * left.surface_area() + n_right as f64 * right.surface_area())
+                    / bounds.surface_area();
+            if cost < best_cost {
+                best_cost = cost;
+                best_split = split;
+            }
+        }
+
+        let mut mid = partition(items, |item| bucket_of(item.centroid[axis]) <= best_split);
+        if mid == 0 || mid == items.len() {
+            // degenerate split, fall back to halving along the axis. total_cmp keeps
+            // the sort from panicking on NaN centroids
+            items.sort_by(|a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+            mid = items.len() / 2;
+        }
+
+        self.nodes.push(BvhNode::Interior {
+            bounds,
+            right: 0,
+            axis,
+        });
+        let (left_items, right_items) = items.split_at_mut(mid);
+        self.build_recursive(left_items);
+        let right_index = self.build_recursive(right_items);
+        if let BvhNode::Interior { right, .. } = &mut self.nodes[node_index] {
+            *right = right_index;
+        }
+        node_index
+    }
+
+    fn push_leaf(&mut self, bounds: Aabb, items: &[BuildItem]) {
+        self.nodes.push(BvhNode::Leaf {
+            bounds,
+            start: self.indices.len(),
+            count: items.len(),
+        });
+        self.indices.extend(items.iter().map(|item| item.index));
+    }
+
+    // Closest hit, `hit_prim` intersects the primitive with the given index. The ray
+    // it gets ends at the closest hit so far.
+    pub fn intersect<'a, F>(&self, ray: &Ray, mut hit_prim: F) -> Option<Intersection<'a>>
+    where
+        F: FnMut(usize, &Ray) -> Option<Intersection<'a>>,
+    {
+        let mut ray = ray.clone();
+        let mut closest: Option<Intersection<'a>> = None;
+        let mut stack = Vec::with_capacity(64);
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if node.bounds().intersect(&ray).is_none() {
+                continue;
+            }
+            match node {
+                BvhNode::Leaf { start, count, .. } => {
+                    for &prim in &self.indices[*start..start + count] {
+                        if let Some(hit) = hit_prim(prim, &ray) {
+                            ray.t_max = hit.t;
+                            closest = Some(hit);
+                        }
+                    }
+                }
+                BvhNode::Interior { right, axis, .. } => {
+                    // visit the near child first
+                    if ray.direction().axis(*axis) < 0. {
+                        stack.push(node_index + 1);
+                        stack.push(*right);
+                    } else {
+                        stack.push(*right);
+                        stack.push(node_index + 1);
+                    }
+                }
+            }
+        }
+        closest
+    }
+
+    // Any hit, stops as soon as `occludes` returns true for a primitive
+    pub fn any_hit<F>(&self, ray: &Ray, mut occludes: F) -> bool
+    where
+        F: FnMut(usize) -> bool,
+    {
+        let mut stack = Vec::with_capacity(64);
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            if node.bounds().intersect(ray).is_none() {
+                continue;
+            }
+            match node {
+                BvhNode::Leaf { start, count, .. } => {
+                    if self.indices[*start..start + count]
+                        .iter()
+                        .any(|&prim| occludes(prim))
+                    {
+                        return true;
+                    }
+                }
+                BvhNode::Interior { right, .. } => {
+                    stack.push(*right);
+                    stack.push(node_index + 1);
+                }
+            }
+        }
+        false
+    }
+}
+
+// In-place partition, returns the number of items for which `pred` holds (moved to the front)
+fn partition<T, P: Fn(&T) -> bool>(items: &mut [T], pred: P) -> usize {
+    let mut first = 0;
+    for i in 0..items.len() {
+        if pred(&items[i]) {
+            items.swap(first, i);
+            first += 1;
+        }
+    }
+    first
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::math::vec3::Vec3;
+    use crate::predef::materials::GLASS;
+    use crate::primitives::{Shadable, Sphere};
+
+    #[test]
+    fn test_matches_brute_force() {
+        // simple LCG so the scene is the same on every run
+        let mut seed: u64 = 7;
+        let mut rand = move || {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 11) as f64 / (1u64 << 53) as f64
+        };
+
+        let spheres: Vec<Sphere> = (0..200)
+            .map(|_| {
+                let p = Point3::new(rand() * 20. - 10., rand() * 20. - 10., rand() * 20. - 30.);
+                Sphere::new(p, 0.2 + rand() * 0.5, GLASS)
+            })
+            .collect();
+        let bounds: Vec<Aabb> = spheres.iter().map(|s| s.bounds().unwrap()).collect();
+        let bvh = Bvh::build(&bounds);
+
+        for _ in 0..500 {
+            let dir = Vec3::new(rand() - 0.5, rand() - 0.5, -rand());
+            let ray = Ray::new_norm(Point3::default(), dir);
+
+            let brute = spheres
+                .iter()
+                .filter_map(|s| s.intersect(&ray))
+                .map(|hit| hit.t)
+                .fold(f64::INFINITY, f64::min);
+            let fast = bvh
+                .intersect(&ray, |i, ray| spheres[i].intersect(ray))
+                .map_or(f64::INFINITY, |hit| hit.t);
+            assert_eq!(brute, fast);
+            assert_eq!(brute.is_finite(), bvh.any_hit(&ray, |i| spheres[i].intersect(&ray).is_some()));
+        }
+    }
+}
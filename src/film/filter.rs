@@ -0,0 +1,99 @@
+use std::f64::consts::PI;
+
+// Pixel reconstruction filters, separable in x and y; radii are in pixels
+#[derive(Debug, Copy, Clone)]
+pub enum Filter {
+    Box { radius: f64 },
+    Tent { radius: f64 },
+    Gaussian { radius: f64, alpha: f64 },
+    // b = c = 1/3 is the pair recommended by Mitchell and Netravali
+    Mitchell { radius: f64, b: f64, c: f64 },
+    // sinc windowed by a wider sinc, `radius` lobes on each side
+    Lanczos { radius: f64 },
+}
+
+impl Filter {
+    pub fn radius(&self) -> f64 {
+        match self {
+            Filter::Box { radius }
+            | Filter::Tent { radius }
+            | Filter::Gaussian { radius, .. }
+            | Filter::Mitchell { radius, .. }
+            | Filter::Lanczos { radius } => *radius,
+        }
+    }
+
+    // Weight of a sample offset (dx, dy) pixels from the pixel center
+    pub fn eval(&self, dx: f64, dy: f64) -> f64 {
+        let r = self.radius();
+        if dx.abs() > r || dy.abs() > r {
+            return 0.;
+        }
+        self.eval_1d(dx) * self.eval_1d(dy)
+    }
+
+    fn eval_1d(&self, x: f64) -> f64 {
+        let x = x.abs();
+        match *self {
+            Filter::Box { .. } => 1.,
+            Filter::Tent { radius } => (radius - x).max(0.),
+            Filter::Gaussian { radius, alpha } => {
+                ((-alpha * x * x).exp() - (-alpha * radius * radius).exp()).max(0.)
+            }
+            Filter::Mitchell { radius, b, c } => mitchell_1d(2. * x / radius, b, c),
+            Filter::Lanczos { radius } => sinc(x) * sinc(x / radius),
+        }
+    }
+}
+
+impl Default for Filter {
+    // one pixel wide box, plain averaging of the samples inside each pixel
+    fn default() -> Self {
+        Filter::Box { radius: 0.5 }
+    }
+}
+
+// Mitchell-Netravali cubic over x in [0, 2]
+fn mitchell_1d(x: f64, b: f64, c: f64) -> f64 {
+    let (x2, x3) = (x * x, x * x * x);
+    let v = if x > 1. {
+        (-b - 6. * c) * x3 + (6. * b + 30. * c) * x2 + (-12. * b - 48. * c) * x + (8. * b + 24. * c)
+    } else {
+        (12. - 9. * b - 6. * c) * x3 + (-18. + 12. * b + 6. * c) * x2 + (6. - 2. * b)
+    };
+    v / 6.
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        return 1.;
+    }
+    let px = PI * x;
+    px.sin() / px
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[allow(unused_imports)]
+    use crate::approx::RelativeEq;
+
+    #[test]
+    fn test_filter_support() {
+        let filters = [
+            Filter::Box { radius: 0.5 },
+            Filter::Tent { radius: 1. },
+            Filter::Gaussian { radius: 1.5, alpha: 2. },
+            Filter::Mitchell { radius: 2., b: 1. / 3., c: 1. / 3. },
+            Filter::Lanczos { radius: 3. },
+        ];
+        for filter in filters.iter() {
+            let r = filter.radius();
+            assert!(filter.eval(0., 0.) > 0.);
+            assert_eq!(filter.eval(r + 0.01, 0.), 0.);
+            assert_relative_eq!(filter.eval(0.1, -0.2), filter.eval(-0.1, 0.2));
+        }
+        assert_relative_eq!(Filter::Gaussian { radius: 1.5, alpha: 2. }.eval(1.5, 0.), 0.);
+        assert_relative_eq!(Filter::Lanczos { radius: 3. }.eval(1., 0.), 0., epsilon = 1e-12);
+    }
+}
@@ -0,0 +1,251 @@
+use image::{ImageBuffer, Rgb};
+
+use crate::primitives::material::Color;
+
+pub mod filter;
+
+use filter::Filter;
+
+#[derive(Debug, Copy, Clone)]
+pub enum ToneMap {
+    // exposure in stops, 0 leaves radiance untouched
+    Linear { exposure: f64 },
+    Reinhard,
+    // luminance that maps to pure white
+    ExtendedReinhard { white_point: f64 },
+    AcesFilmic,
+}
+
+impl ToneMap {
+    pub fn apply(&self, col: &Color) -> Color {
+        match self {
+            ToneMap::Linear { exposure } => col.mult(exposure.exp2()),
+            ToneMap::Reinhard => scale_luminance(col, |l| l / (1. + l)),
+            ToneMap::ExtendedReinhard { white_point } => {
+                let w2 = white_point * white_point;
+                scale_luminance(col, |l| l * (1. + l / w2) / (1. + l))
+            }
+            ToneMap::AcesFilmic => Color::new(aces(col.red), aces(col.green), aces(col.blue)),
+        }
+    }
+}
+
+impl Default for ToneMap {
+    fn default() -> Self {
+        ToneMap::Linear { exposure: 0. }
+    }
+}
+
+// Maps luminance only so hues are preserved
+fn scale_luminance<F: Fn(f64) -> f64>(col: &Color, curve: F) -> Color {
+    let l = col.luminance();
+    if l <= 0. {
+        return Color::new(0., 0., 0.);
+    }
+    col.mult(curve(l) / l)
+}
+
+// Narkowicz's fit of the ACES reference rendering transform
+fn aces(x: f64) -> f64 {
+    let x = x * 0.6; // the fit expects pre-exposed input
+    ((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)).clamp(0., 1.)
+}
+
+// sRGB transfer function for linear values in [0, 1]
+pub fn srgb_encode(c: f64) -> f64 {
+    let c = c.clamp(0., 1.);
+    if c <= 0.003_130_8 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1. / 2.4) - 0.055
+    }
+}
+
+// Floating point image that accumulates weighted, unclamped radiance samples
+#[derive(Debug)]
+pub struct Film {
+    x0: u32, // top left pixel of the window this film covers
+    y0: u32,
+    width: u32,
+    height: u32,
+    sums: Vec<Color>,
+    weights: Vec<f64>,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32) -> Film {
+        Film::with_window(0, 0, width, height)
+    }
+
+    // Film covering pixels [x0, x0 + width) x [y0, y0 + height) of a larger image,
+    // pixels are still addressed in the coordinates of the full image
+    pub fn with_window(x0: u32, y0: u32, width: u32, height: u32) -> Film {
+        let size = (width * height) as usize;
+        Film {
+            x0,
+            y0,
+            width,
+            height,
+            sums: vec![Color::new(0., 0., 0.); size],
+            weights: vec![0.; size],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        let (lx, ly) = (x.wrapping_sub(self.x0), y.wrapping_sub(self.y0));
+        assert!(lx < self.width && ly < self.height, "Pixel {}, {} outside the film", x, y);
+        (ly * self.width + lx) as usize
+    }
+
+    pub fn add_sample(&mut self, x: u32, y: u32, col: &Color, weight: f64) {
+        let i = self.index(x, y);
+        // Mitchell and Lanczos have negative lobes, Color::mult only takes positive factors
+        let weighted = Color::new(col.red * weight, col.green * weight, col.blue * weight);
+        self.sums[i].add(&weighted);
+        self.weights[i] += weight;
+    }
+
+    // Adds a sample at continuous raster position (x, y) to every pixel under the filter
+    pub fn splat(&mut self, x: f64, y: f64, col: &Color, filter: &Filter) {
+        let r = filter.radius();
+        // pixel centers sit at half integer coordinates
+        let x0 = (x - 0.5 - r).ceil().max(f64::from(self.x0)) as u32;
+        let y0 = (y - 0.5 - r).ceil().max(f64::from(self.y0)) as u32;
+        let x1 = ((x - 0.5 + r).floor() as i64).min(i64::from(self.x0 + self.width) - 1);
+        let y1 = ((y - 0.5 + r).floor() as i64).min(i64::from(self.y0 + self.height) - 1);
+        for py in i64::from(y0)..=y1 {
+            for px in i64::from(x0)..=x1 {
+                let weight = filter.eval(px as f64 + 0.5 - x, py as f64 + 0.5 - y);
+                if weight != 0. {
+                    self.add_sample(px as u32, py as u32, col, weight);
+                }
+            }
+        }
+    }
+
+    // Adds everything accumulated in `tile` into the matching pixels of this film
+    pub fn merge(&mut self, tile: &Film) {
+        for ty in 0..tile.height {
+            for tx in 0..tile.width {
+                let t = (ty * tile.width + tx) as usize;
+                let i = self.index(tile.x0 + tx, tile.y0 + ty);
+                self.sums[i].add(&tile.sums[t]);
+                self.weights[i] += tile.weights[t];
+            }
+        }
+    }
+
+    // Weighted average radiance, black where nothing was sampled
+    pub fn pixel(&self, x: u32, y: u32) -> Color {
+        let i = self.index(x, y);
+        if self.weights[i] > 0. {
+            self.sums[i].mult(1. / self.weights[i])
+        } else {
+            Color::new(0., 0., 0.)
+        }
+    }
+
+    // Tone maps and sRGB encodes every pixel into an 8 bit image
+    pub fn develop(&self, tone_map: &ToneMap) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            let mapped = tone_map.apply(&self.pixel(self.x0 + x, self.y0 + y));
+            Color::new(
+                srgb_encode(mapped.red),
+                srgb_encode(mapped.green),
+                srgb_encode(mapped.blue),
+            )
+            .to_rgb()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[allow(unused_imports)]
+    use crate::approx::RelativeEq;
+
+    #[test]
+    fn test_srgb_encode() {
+        assert_eq!(srgb_encode(0.), 0.);
+        assert_relative_eq!(srgb_encode(1.), 1., epsilon = 1e-12);
+        assert_eq!(Color::new(srgb_encode(0.5), 0., 0.).to_rgb()[0], 188);
+    }
+
+    #[test]
+    fn test_tone_maps_stay_in_range() {
+        let maps = [
+            ToneMap::Reinhard,
+            ToneMap::ExtendedReinhard { white_point: 4. },
+            ToneMap::AcesFilmic,
+        ];
+        for map in maps.iter() {
+            let mut last = 0.;
+            for i in 0..=8 {
+                let v = f64::from(i) * 0.5;
+                let out = map.apply(&Color::new(v, v, v)).green;
+                assert!(out >= last && out <= 1. + 1e-9);
+                last = out;
+            }
+        }
+        let white = ToneMap::ExtendedReinhard { white_point: 4. }.apply(&Color::new(4., 4., 4.));
+        assert_relative_eq!(white.red, 1., epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_accumulate() {
+        let mut film = Film::new(2, 2);
+        film.add_sample(1, 0, &Color::new(3., 0., 0.), 1.);
+        film.add_sample(1, 0, &Color::new(1., 0., 0.), 1.);
+        assert_relative_eq!(film.pixel(1, 0).red, 2.);
+        assert_eq!(film.develop(&ToneMap::default()).get_pixel(1, 0)[0], 255);
+    }
+
+    #[test]
+    fn test_splat_reaches_neighbours() {
+        let mut film = Film::new(3, 3);
+        let tent = Filter::Tent { radius: 1. };
+        film.splat(1.5, 1.5, &Color::new(1., 1., 1.), &tent);
+        film.splat(2.5, 1.5, &Color::new(0., 0., 0.), &tent);
+        assert_relative_eq!(film.pixel(1, 1).red, 1.);
+        assert_relative_eq!(film.pixel(2, 1).red, 0.);
+        assert_eq!(film.weights[film.index(0, 0)], 0.);
+
+        let mut film = Film::new(3, 3);
+        film.splat(1.9, 1.5, &Color::new(1., 1., 1.), &tent);
+        film.splat(2.5, 1.5, &Color::new(0., 0., 0.), &tent);
+        assert_relative_eq!(film.pixel(2, 1).red, 0.4 / 1.4);
+    }
+
+    #[test]
+    fn test_merge_tiles() {
+        let tent = Filter::Tent { radius: 1. };
+        let mut whole = Film::new(4, 2);
+        whole.splat(1.9, 0.5, &Color::new(1., 0., 0.), &tent);
+        whole.splat(2.2, 1.5, &Color::new(0., 1., 0.), &tent);
+
+        // each tile only sees its own sample but keeps the overlap into its neighbour
+        let mut left = Film::with_window(0, 0, 3, 2);
+        left.splat(1.9, 0.5, &Color::new(1., 0., 0.), &tent);
+        let mut right = Film::with_window(1, 0, 3, 2);
+        right.splat(2.2, 1.5, &Color::new(0., 1., 0.), &tent);
+        let mut merged = Film::new(4, 2);
+        merged.merge(&left);
+        merged.merge(&right);
+
+        for y in 0..2 {
+            for x in 0..4 {
+                assert_relative_eq!(merged.pixel(x, y).red, whole.pixel(x, y).red);
+                assert_relative_eq!(merged.pixel(x, y).green, whole.pixel(x, y).green);
+            }
+        }
+    }
+}
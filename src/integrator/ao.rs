@@ -0,0 +1,48 @@
+use crate::math::ray::Ray;
+use crate::primitives::material::Color;
+use crate::sampler;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+
+use super::Integrator;
+
+// Fraction of the cosine weighted hemisphere that is unoccluded within `distance`
+#[derive(Debug)]
+pub struct AoIntegrator {
+    samples: u32,
+    distance: f64,
+}
+
+impl AoIntegrator {
+    pub fn new(samples: u32, distance: f64) -> AoIntegrator {
+        AoIntegrator { samples, distance }
+    }
+}
+
+impl Default for AoIntegrator {
+    fn default() -> Self {
+        AoIntegrator::new(16, 1.)
+    }
+}
+
+impl Integrator for AoIntegrator {
+    fn li(&self, scene: &Scene, ray: &Ray, sampler: &mut Sampler) -> Color {
+        let hit = match scene.find_nearest_intersect(ray) {
+            Some(hit) => hit,
+            None => return Color::new(1., 1., 1.),
+        };
+        let normal = hit.facing_normal();
+
+        let mut unoccluded = 0;
+        for _ in 0..self.samples {
+            let local = sampler::cosine_hemisphere(sampler.next_2d());
+            let mut ao_ray = hit.spawn_ray(sampler::to_world(&local, &normal));
+            ao_ray.t_max = self.distance;
+            if !scene.is_occluded(&ao_ray) {
+                unoccluded += 1;
+            }
+        }
+        let visibility = f64::from(unoccluded) / f64::from(self.samples.max(1));
+        Color::new(visibility, visibility, visibility)
+    }
+}
@@ -0,0 +1,55 @@
+use crate::math::ray::Ray;
+use crate::predef::colors;
+use crate::primitives::material::Color;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+
+use super::Integrator;
+
+// Maps the world space surface normal from [-1,1] to an rgb color
+#[derive(Debug, Default)]
+pub struct NormalIntegrator;
+
+impl Integrator for NormalIntegrator {
+    fn li(&self, scene: &Scene, ray: &Ray, _sampler: &mut Sampler) -> Color {
+        match scene.find_nearest_intersect(ray) {
+            Some(hit) => Color::new(
+                hit.shading_normal.x() * 0.5 + 0.5,
+                hit.shading_normal.y() * 0.5 + 0.5,
+                hit.shading_normal.z() * 0.5 + 0.5,
+            ),
+            None => colors::BLACK,
+        }
+    }
+}
+
+// White close to the camera fading to black at `max_distance`
+#[derive(Debug)]
+pub struct DepthIntegrator {
+    max_distance: f64,
+}
+
+impl DepthIntegrator {
+    pub fn new(max_distance: f64) -> DepthIntegrator {
+        DepthIntegrator { max_distance }
+    }
+}
+
+impl Default for DepthIntegrator {
+    fn default() -> Self {
+        DepthIntegrator::new(10.)
+    }
+}
+
+impl Integrator for DepthIntegrator {
+    fn li(&self, scene: &Scene, ray: &Ray, _sampler: &mut Sampler) -> Color {
+        match scene.find_nearest_intersect(ray) {
+            Some(hit) => {
+                let dist = hit.t * ray.direction().mag();
+                let grey = (1. - dist / self.max_distance).clamp(0., 1.);
+                Color::new(grey, grey, grey)
+            }
+            None => colors::BLACK,
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use crate::math::ray::Ray;
+use crate::primitives::material::Color;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+
+pub mod ao;
+pub mod debug;
+pub mod path;
+pub mod whitted;
+
+pub use ao::AoIntegrator;
+pub use debug::{DepthIntegrator, NormalIntegrator};
+pub use path::PathIntegrator;
+pub use whitted::WhittedIntegrator;
+
+// Computes the radiance arriving along a camera ray
+pub trait Integrator: std::fmt::Debug + Send + Sync {
+    fn li(&self, scene: &Scene, ray: &Ray, sampler: &mut Sampler) -> Color;
+}
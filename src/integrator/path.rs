@@ -0,0 +1,137 @@
+use std::f64::consts::PI;
+
+use crate::math::ray::Ray;
+use crate::predef::colors;
+use crate::primitives::material::Color;
+use crate::primitives::material::Material;
+use crate::sampler;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+
+use super::Integrator;
+
+const RR_START_DEPTH: u32 = 3; // bounces before russian roulette may end a path
+
+// Unidirectional path tracing with next event estimation
+#[derive(Debug)]
+pub struct PathIntegrator {
+    max_depth: u32,
+}
+
+impl PathIntegrator {
+    pub fn new(max_depth: u32) -> PathIntegrator {
+        PathIntegrator { max_depth }
+    }
+}
+
+impl Default for PathIntegrator {
+    fn default() -> Self {
+        PathIntegrator::new(64)
+    }
+}
+
+impl Integrator for PathIntegrator {
+    fn li(&self, scene: &Scene, ray: &Ray, sampler: &mut Sampler) -> Color {
+        let mut radiance = colors::BLACK;
+        let mut throughput = colors::WHITE;
+        let mut ray = ray.clone();
+        let mut specular_bounce = false;
+
+        for depth in 0..self.max_depth {
+            let hit = scene.find_nearest_intersect(&ray);
+            // light seen through a diffuse bounce is already counted by next event estimation
+            if let Some(emitted) = scene.emitter_before(&ray, hit.as_ref()) {
+                if depth == 0 || specular_bounce {
+                    radiance.add(&throughput.mult_col(&emitted));
+                }
+                break;
+            }
+            let hit = match hit {
+                Some(hit) => hit,
+                None => {
+                    radiance.add(&throughput.mult_col(scene.background_col()));
+                    break;
+                }
+            };
+
+            // shade on the side the ray arrived from
+            let normal = hit.facing_normal();
+
+            // mixed materials follow one lobe, picking it with probability equal to its
+            // weight cancels the weight out
+            let material = match hit.mat() {
+                Material::Mixed { diff_col, spec_col, spec_factor } => {
+                    if sampler.next_f64() < spec_factor {
+                        Material::specular(spec_col)
+                    } else {
+                        Material::diffuse(diff_col)
+                    }
+                }
+                other => other,
+            };
+
+            match material {
+                Material::Diffuse { diff_col } => {
+                    let p = hit.point();
+                    let brdf = diff_col.mult(1. / PI);
+                    for light in scene.lights().iter() {
+                        let n = light.samples();
+                        for _ in 0..n {
+                            let sample = light.trace_light(&p, sampler.next_2d());
+                            let cos = sample.ray.direction().dot(&normal);
+                            if sample.pdf > 0. && cos > 0. && !scene.is_occluded(&hit.shadow_ray(&sample)) {
+                                let weight = cos / (sample.pdf * f64::from(n));
+                                let direct = sample.radiance.mult_col(&brdf).mult(weight);
+                                radiance.add(&throughput.mult_col(&direct));
+                            }
+                        }
+                    }
+
+                    // brdf * cos / pdf reduces to the albedo for cosine sampling
+                    let local = sampler::cosine_hemisphere(sampler.next_2d());
+                    let dir = sampler::to_world(&local, &normal);
+                    throughput = throughput.mult_col(&diff_col);
+                    ray = hit.spawn_ray(dir);
+                    specular_bounce = false;
+                }
+
+                Material::Specular { spec_col } => {
+                    throughput = throughput.mult_col(&spec_col);
+                    ray = hit.reflect();
+                    specular_bounce = true;
+                }
+
+                Material::Refractive { spec_col, refr_col, .. } => {
+                    if hit.is_false_hit() {
+                        ray = hit.pass_through();
+                        continue;
+                    }
+                    // follow one side in proportion to the Fresnel reflectance, TIR always reflects
+                    let kr = hit.fresnel();
+                    match hit.refract() {
+                        Some(refr_ray) if sampler.next_f64() >= kr => {
+                            throughput = throughput.mult_col(&refr_col);
+                            ray = refr_ray;
+                        }
+                        _ => {
+                            throughput = throughput.mult_col(&spec_col);
+                            ray = hit.reflect();
+                        }
+                    }
+                    specular_bounce = true;
+                }
+
+                Material::Mixed { .. } => unreachable!("mixed materials pick a lobe above"),
+            }
+
+            if depth >= RR_START_DEPTH {
+                let survive = throughput.max_component().min(0.95);
+                if sampler.next_f64() >= survive {
+                    break;
+                }
+                throughput = throughput.mult(1. / survive);
+            }
+        }
+        radiance
+    }
+}
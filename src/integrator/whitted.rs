@@ -0,0 +1,124 @@
+use std::f64::consts::PI;
+
+use crate::math::ray::Ray;
+use crate::primitives::material::Color;
+use crate::primitives::material::Material;
+use crate::primitives::Intersection;
+use crate::sampler::Sampler;
+use crate::scene::Scene;
+
+use super::Integrator;
+
+// Rays that can change the pixel by less than this are not traced, stops the ray
+// tree from branching at every refractive hit down to max_depth
+const MIN_WEIGHT: f64 = 1e-3;
+
+// Recursive ray tracing, direct light on diffuse surfaces and perfect reflection/refraction
+#[derive(Debug)]
+pub struct WhittedIntegrator {
+    max_depth: u32,
+}
+
+impl WhittedIntegrator {
+    pub fn new(max_depth: u32) -> WhittedIntegrator {
+        WhittedIntegrator { max_depth }
+    }
+
+    // Color seen along a secondary ray that contributes `weight` to the pixel
+    fn trace(&self, scene: &Scene, ray: &Ray, sampler: &mut Sampler, depth: u32, weight: f64) -> Color {
+        if weight < MIN_WEIGHT {
+            return Color::new(0., 0., 0.);
+        }
+        let hit = scene.find_nearest_intersect(ray);
+        if let Some(emitted) = scene.emitter_before(ray, hit.as_ref()) {
+            return emitted;
+        }
+        match hit {
+            Some(intersection) => self.shade(scene, intersection, sampler, depth + 1, weight),
+            None => *scene.background_col(),
+        }
+    }
+
+    // Direct light from every light source, area lights are averaged over several
+    // shadow rays which gives soft shadows
+    fn diffuse(&self, scene: &Scene, hit: &Intersection, diff_col: &Color, sampler: &mut Sampler) -> Color {
+        let mut col = Color::new(0., 0., 0.);
+        let hit_point = hit.point();
+        let normal = hit.facing_normal();
+        let brdf = diff_col.mult(1. / PI);
+        for light in scene.lights().iter() {
+            let n = light.samples();
+            for _ in 0..n {
+                let sample = light.trace_light(&hit_point, sampler.next_2d());
+                let cos = sample.ray.direction().dot(&normal);
+                if sample.pdf > 0. && cos > 0. && !scene.is_occluded(&hit.shadow_ray(&sample)) {
+                    let weight = cos / (sample.pdf * f64::from(n));
+                    col.add(&sample.radiance.mult_col(&brdf).mult(weight));
+                }
+            }
+        }
+        col
+    }
+
+    // Perfect mirror, the reflected radiance is tinted by the specular color
+    fn specular(&self, scene: &Scene, hit: &Intersection, spec_col: &Color, sampler: &mut Sampler, depth: u32, weight: f64) -> Color {
+        let refl_weight = weight * spec_col.max_component();
+        self.trace(scene, &hit.reflect(), sampler, depth, refl_weight).mult_col(spec_col)
+    }
+
+    fn shade(&self, scene: &Scene, hit: Intersection, sampler: &mut Sampler, depth: u32, weight: f64) -> Color {
+        if depth == self.max_depth {
+            return *scene.background_col();
+        }
+
+        match hit.mat() {
+            Material::Diffuse { diff_col } => self.diffuse(scene, &hit, &diff_col, sampler),
+
+            Material::Specular { spec_col } => self.specular(scene, &hit, &spec_col, sampler, depth, weight),
+
+            Material::Mixed { diff_col, spec_col, spec_factor } => {
+                let diffuse = self.diffuse(scene, &hit, &diff_col, sampler);
+                if weight * spec_factor < MIN_WEIGHT {
+                    return diffuse.mult(1. - spec_factor);
+                }
+                let specular = self.specular(scene, &hit, &spec_col, sampler, depth, weight * spec_factor);
+                specular.mix(&diffuse, spec_factor)
+            },
+
+            Material::Refractive { spec_col, refr_col, .. } => {
+                if hit.is_false_hit() {
+                    return self.trace(scene, &hit.pass_through(), sampler, depth, weight);
+                }
+                // total internal reflection gives kr = 1 and sends everything into reflection
+                let kr = hit.fresnel();
+                let refl_weight = weight * kr * spec_col.max_component();
+                let mut col = self.trace(scene, &hit.reflect(), sampler, depth, refl_weight).mult_col(&spec_col).mult(kr);
+                if let Some(refr_ray) = hit.refract() {
+                    let refr_weight = weight * (1. - kr) * refr_col.max_component();
+                    let refracted = self.trace(scene, &refr_ray, sampler, depth, refr_weight).mult_col(&refr_col);
+                    col.add(&refracted.mult(1. - kr));
+                }
+                col
+            },
+        }
+    }
+}
+
+impl Default for WhittedIntegrator {
+    fn default() -> Self {
+        WhittedIntegrator::new(64)
+    }
+}
+
+impl Integrator for WhittedIntegrator {
+    fn li(&self, scene: &Scene, ray: &Ray, sampler: &mut Sampler) -> Color {
+        let hit = scene.find_nearest_intersect(ray);
+        if let Some(emitted) = scene.emitter_before(ray, hit.as_ref()) {
+            return emitted;
+        }
+        match hit {
+            Some(intersection) => self.shade(scene, intersection, sampler, 0, 1.),
+            None => *scene.background_col(),
+        }
+    }
+}
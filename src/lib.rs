@@ -2,8 +2,13 @@
 #[macro_use]
 extern crate approx;
 //extern crate rand;
+pub mod accel;
+pub mod film;
+pub mod integrator;
+pub mod loader;
 pub mod math;
 pub mod primitives;
+pub mod sampler;
 pub mod scene;
 pub mod predef;
 
@@ -0,0 +1,97 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+pub mod mtl;
+pub mod obj;
+pub mod scene;
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io {
+        file: String,
+        source: io::Error,
+    },
+    Parse {
+        file: String,
+        line: usize,
+        column: Option<usize>, // 1-based, None when the whole line is at fault
+        message: String,
+    },
+}
+
+impl LoadError {
+    pub fn parse<S: Into<String>>(file: &str, line: usize, message: S) -> LoadError {
+        LoadError::Parse {
+            file: file.to_string(),
+            line,
+            column: None,
+            message: message.into(),
+        }
+    }
+
+    pub fn parse_at<S: Into<String>>(file: &str, line: usize, column: usize, message: S) -> LoadError {
+        LoadError::Parse {
+            file: file.to_string(),
+            line,
+            column: Some(column),
+            message: message.into(),
+        }
+    }
+
+    pub fn io(file: &str, source: io::Error) -> LoadError {
+        LoadError::Io {
+            file: file.to_string(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io { file, source } => write!(f, "{}: {}", file, source),
+            LoadError::Parse {
+                file,
+                line,
+                column: Some(column),
+                message,
+            } => write!(f, "{}:{}:{}: {}", file, line, column, message),
+            LoadError::Parse {
+                file,
+                line,
+                column: None,
+                message,
+            } => write!(f, "{}:{}: {}", file, line, message),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Io { source, .. } => Some(source),
+            LoadError::Parse { .. } => None,
+        }
+    }
+}
+
+// 1-based column at which token starts, token must be a slice of source_line
+pub(crate) fn column_of(source_line: &str, token: &str) -> usize {
+    let offset = token.as_ptr() as usize - source_line.as_ptr() as usize;
+    source_line[..offset].chars().count() + 1
+}
+
+// Parses every whitespace separated argument of source_line as a finite float
+pub(crate) fn parse_floats(args: &[&str], source_line: &str, file: &str, line: usize) -> Result<Vec<f64>, LoadError> {
+    args.iter()
+        .map(|arg| {
+            let column = column_of(source_line, arg);
+            match arg.parse::<f64>() {
+                Ok(value) if value.is_finite() => Ok(value),
+                Ok(_) => Err(LoadError::parse_at(file, line, column, format!("'{}' is not a finite number", arg))),
+                Err(_) => Err(LoadError::parse_at(file, line, column, format!("invalid number '{}'", arg))),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::fs;
+
+use crate::loader::{parse_floats, LoadError};
+use crate::primitives::material::{Color, Material};
+
+// The subset of an MTL material that maps onto our Material variants
+#[derive(Debug)]
+struct MtlMaterial {
+    kd: Color,
+    ks: Color,
+    tf: Option<Color>,
+    ni: f64,
+    dissolve: f64,
+    illum: u32,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        MtlMaterial {
+            kd: Color::new(0.8, 0.8, 0.8),
+            ks: Color::new(0., 0., 0.),
+            tf: None,
+            ni: 1.,
+            dissolve: 1.,
+            illum: 2,
+        }
+    }
+}
+
+impl MtlMaterial {
+    // Closest Material variant, see the illum models in the MTL spec
+    fn to_material(&self) -> Material {
+        let transparent = self.dissolve < 1. || [4, 6, 7, 9].contains(&self.illum);
+        let kd_lum = self.kd.luminance();
+        let ks_lum = self.ks.luminance();
+
+        if transparent {
+            let spec = if ks_lum > 0. { self.ks } else { Color::new(1., 1., 1.) };
+            let refr = self.tf.unwrap_or_else(|| Color::new(1., 1., 1.));
+            Material::refractive(spec, refr, self.ni)
+        } else if self.illum < 2 || ks_lum <= 0. {
+            Material::diffuse(self.kd)
+        } else if kd_lum <= 0. {
+            Material::specular(self.ks)
+        } else {
+            Material::mixed(self.kd, self.ks, ks_lum / (kd_lum + ks_lum))
+        }
+    }
+}
+
+fn parse_color(args: &[&str], source_line: &str, file: &str, line: usize) -> Result<Color, LoadError> {
+    if args.first() == Some(&"spectral") || args.first() == Some(&"xyz") {
+        return Err(LoadError::parse(file, line, "only rgb colors are supported"));
+    }
+    let values = parse_floats(args, source_line, file, line)?;
+    match values.len() {
+        1 => Ok(Color::new(values[0], values[0], values[0])),
+        3 => Ok(Color::new(values[0], values[1], values[2])),
+        n => Err(LoadError::parse(file, line, format!("expected 1 or 3 color values, found {}", n))),
+    }
+}
+
+fn parse_scalar(args: &[&str], source_line: &str, file: &str, line: usize) -> Result<f64, LoadError> {
+    match parse_floats(args, source_line, file, line)?.as_slice() {
+        [value] => Ok(*value),
+        _ => Err(LoadError::parse(file, line, "expected a single value")),
+    }
+}
+
+// Used for faces without a usemtl statement
+pub fn default_material() -> Material {
+    MtlMaterial::default().to_material()
+}
+
+pub fn parse(source: &str, file: &str) -> Result<HashMap<String, Material>, LoadError> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+
+    for (i, raw_line) in source.lines().enumerate() {
+        let line = i + 1;
+        let content = raw_line.split('#').next().unwrap_or("");
+        let mut tokens = content.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(k) => k,
+            None => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            if let Some((name, mtl)) = current.take() {
+                materials.insert(name, mtl.to_material());
+            }
+            if args.is_empty() {
+                return Err(LoadError::parse(file, line, "newmtl without a name"));
+            }
+            current = Some((args.join(" "), MtlMaterial::default()));
+            continue;
+        }
+
+        let mtl = match current.as_mut() {
+            Some((_, mtl)) => mtl,
+            None => return Err(LoadError::parse(file, line, format!("'{}' before any newmtl", keyword))),
+        };
+        match keyword {
+            "Kd" => mtl.kd = parse_color(&args, raw_line, file, line)?,
+            "Ks" => mtl.ks = parse_color(&args, raw_line, file, line)?,
+            "Tf" => mtl.tf = Some(parse_color(&args, raw_line, file, line)?),
+            "Ni" => mtl.ni = parse_scalar(&args, raw_line, file, line)?,
+            "d" => mtl.dissolve = parse_scalar(&args, raw_line, file, line)?,
+            "Tr" => mtl.dissolve = 1. - parse_scalar(&args, raw_line, file, line)?,
+            "illum" => {
+                mtl.illum = args
+                    .first()
+                    .and_then(|a| a.parse().ok())
+                    .ok_or_else(|| LoadError::parse(file, line, "invalid illum model"))?
+            }
+            _ => {} // Ka, Ns, texture maps etc. have no equivalent yet
+        }
+    }
+    if let Some((name, mtl)) = current.take() {
+        materials.insert(name, mtl.to_material());
+    }
+    Ok(materials)
+}
+
+pub fn load(path: &str) -> Result<HashMap<String, Material>, LoadError> {
+    let source = fs::read_to_string(path).map_err(|e| LoadError::io(path, e))?;
+    parse(&source, path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_material_mapping() {
+        let source = "
+            newmtl matte
+            Kd 0.5 0.2 0.1
+            newmtl mirror
+            Kd 0 0 0
+            Ks 0.9 0.9 0.9
+            newmtl plastic # varnished
+            Kd 0.5 0.2 0.1
+            Ks 0.5 0.5 0.5
+            newmtl glass
+            Ni 1.5
+            d 0.1
+        ";
+        let materials = parse(source, "test.mtl").unwrap();
+        assert!(matches!(materials["matte"], Material::Diffuse { .. }));
+        assert!(matches!(materials["mirror"], Material::Specular { .. }));
+        assert!(matches!(materials["plastic"], Material::Mixed { .. }));
+        match materials["glass"] {
+            Material::Refractive { ior, .. } => assert_eq!(ior, 1.5),
+            _ => panic!("glass should be refractive"),
+        }
+    }
+
+    #[test]
+    fn test_error_line() {
+        let err = parse("newmtl a\nKd 1 x 1\n", "bad.mtl").unwrap_err();
+        assert_eq!(err.to_string(), "bad.mtl:2:6: invalid number 'x'");
+        let err = parse("newmtl a\nNi inf\n", "bad.mtl").unwrap_err();
+        assert_eq!(err.to_string(), "bad.mtl:2:4: 'inf' is not a finite number");
+    }
+}
@@ -0,0 +1,361 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::loader::{mtl, parse_floats, LoadError};
+use crate::math::point3::Point3;
+use crate::math::vec3::Vec3;
+use crate::primitives::material::Material;
+use crate::primitives::mesh::TriangleMesh;
+
+// Zero based (position, uv, normal) indices of one face corner
+type Corner = (usize, Option<usize>, Option<usize>);
+
+// Triangles sharing a group and material, each becomes its own mesh
+struct FaceGroup {
+    material: Option<String>,
+    triangles: Vec<[Corner; 3]>,
+}
+
+// OBJ indices are 1-based, negative values count back from the last element read so far
+fn resolve_index(token: &str, count: usize, file: &str, line: usize) -> Result<usize, LoadError> {
+    let index: i64 = token
+        .parse()
+        .map_err(|_| LoadError::parse(file, line, format!("invalid index '{}'", token)))?;
+    let resolved = if index > 0 {
+        index - 1
+    } else if index < 0 {
+        count as i64 + index
+    } else {
+        return Err(LoadError::parse(file, line, "index 0 is not valid in OBJ files"));
+    };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(LoadError::parse(
+            file,
+            line,
+            format!("index {} out of range, only {} elements defined", index, count),
+        ));
+    }
+    Ok(resolved as usize)
+}
+
+fn parse_corner(
+    token: &str,
+    counts: (usize, usize, usize),
+    file: &str,
+    line: usize,
+) -> Result<Corner, LoadError> {
+    let mut parts = token.split('/');
+    let v = resolve_index(parts.next().unwrap_or(""), counts.0, file, line)?;
+    let vt = match parts.next() {
+        Some("") | None => None,
+        Some(t) => Some(resolve_index(t, counts.1, file, line)?),
+    };
+    let vn = match parts.next() {
+        Some("") | None => None,
+        Some(t) => Some(resolve_index(t, counts.2, file, line)?),
+    };
+    if parts.next().is_some() {
+        return Err(LoadError::parse(file, line, format!("malformed face vertex '{}'", token)));
+    }
+    Ok((v, vt, vn))
+}
+
+fn cross_2d(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+// Ear clipping in the polygon's dominant plane, keeps the original winding
+fn triangulate(points: &[Point3]) -> Vec<[usize; 3]> {
+    let n = points.len();
+    let fan = |start: &[usize]| -> Vec<[usize; 3]> {
+        (1..start.len() - 1)
+            .map(|i| [start[0], start[i], start[i + 1]])
+            .collect()
+    };
+    let all: Vec<usize> = (0..n).collect();
+    if n == 3 {
+        return fan(&all);
+    }
+
+    // Newell's method for the polygon normal
+    let mut normal = Vec3::new(0., 0., 0.);
+    for i in 0..n {
+        let (cur, next) = (&points[i], &points[(i + 1) % n]);
+        normal.add_mut(&Vec3::new(
+            (cur.y() - next.y()) * (cur.z() + next.z()),
+            (cur.z() - next.z()) * (cur.x() + next.x()),
+            (cur.x() - next.x()) * (cur.y() + next.y()),
+        ));
+    }
+    let drop = (0..3)
+        .max_by(|a, b| normal.axis(*a).abs().partial_cmp(&normal.axis(*b).abs()).unwrap_or(Ordering::Equal))
+        .unwrap();
+    if normal.axis(drop) == 0. {
+        return fan(&all);
+    }
+    let (u, v) = ((drop + 1) % 3, (drop + 2) % 3);
+    let projected: Vec<(f64, f64)> = points.iter().map(|p| (p.axis(u), p.axis(v))).collect();
+    let orientation = normal.axis(drop).signum();
+
+    let mut remaining = all;
+    let mut triangles = Vec::with_capacity(n - 2);
+    while remaining.len() > 3 {
+        let len = remaining.len();
+        let ear = (0..len).find(|&i| {
+            let (a, b, c) = (remaining[(i + len - 1) % len], remaining[i], remaining[(i + 1) % len]);
+            let (pa, pb, pc) = (projected[a], projected[b], projected[c]);
+            if cross_2d(pa, pb, pc) * orientation <= 0. {
+                return false; // reflex or degenerate corner
+            }
+            !remaining.iter().any(|&other| {
+                other != a
+                    && other != b
+                    && other != c
+                    && cross_2d(pa, pb, projected[other]) * orientation >= 0.
+                    && cross_2d(pb, pc, projected[other]) * orientation >= 0.
+                    && cross_2d(pc, pa, projected[other]) * orientation >= 0.
+            })
+        });
+        match ear {
+            Some(i) => {
+                triangles.push([remaining[(i + len - 1) % len], remaining[i], remaining[(i + 1) % len]]);
+                remaining.remove(i);
+            }
+            None => {
+                // self intersecting or otherwise broken polygon, do what we can
+                triangles.extend(fan(&remaining));
+                return triangles;
+            }
+        }
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+    triangles
+}
+
+// `load_mtl` resolves the files named by mtllib statements
+pub fn parse<F>(source: &str, file: &str, mut load_mtl: F) -> Result<Vec<TriangleMesh>, LoadError>
+where
+    F: FnMut(&str) -> Result<HashMap<String, Material>, LoadError>,
+{
+    let mut positions: Vec<Point3> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<(f64, f64)> = Vec::new();
+    let mut materials: HashMap<String, Material> = HashMap::new();
+
+    let mut groups: Vec<FaceGroup> = Vec::new();
+    let mut group_lookup: HashMap<(String, Option<String>), usize> = HashMap::new();
+    let mut group_name = String::from("default");
+    let mut material_name: Option<String> = None;
+
+    for (i, raw_line) in source.lines().enumerate() {
+        let line = i + 1;
+        let content = raw_line.split('#').next().unwrap_or("");
+        let mut tokens = content.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(k) => k,
+            None => continue,
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => match parse_floats(&args, raw_line, file, line)?.as_slice() {
+                [x, y, z] | [x, y, z, _] => positions.push(Point3::new(*x, *y, *z)),
+                _ => return Err(LoadError::parse(file, line, "expected 3 or 4 values for v")),
+            },
+            "vn" => match parse_floats(&args, raw_line, file, line)?.as_slice() {
+                [x, y, z] => normals.push(Vec3::new(*x, *y, *z).norm()),
+                _ => return Err(LoadError::parse(file, line, "expected 3 values for vn")),
+            },
+            "vt" => match parse_floats(&args, raw_line, file, line)?.as_slice() {
+                [u] => uvs.push((*u, 0.)),
+                [u, v] | [u, v, _] => uvs.push((*u, *v)),
+                _ => return Err(LoadError::parse(file, line, "expected 1 to 3 values for vt")),
+            },
+            "f" => {
+                if args.len() < 3 {
+                    return Err(LoadError::parse(file, line, "a face needs at least 3 vertices"));
+                }
+                let counts = (positions.len(), uvs.len(), normals.len());
+                let corners = args
+                    .iter()
+                    .map(|token| parse_corner(token, counts, file, line))
+                    .collect::<Result<Vec<Corner>, LoadError>>()?;
+                let points: Vec<Point3> = corners.iter().map(|c| positions[c.0]).collect();
+
+                let key = (group_name.clone(), material_name.clone());
+                let index = *group_lookup.entry(key).or_insert_with(|| {
+                    groups.push(FaceGroup {
+                        material: material_name.clone(),
+                        triangles: Vec::new(),
+                    });
+                    groups.len() - 1
+                });
+                for [a, b, c] in triangulate(&points) {
+                    groups[index].triangles.push([corners[a], corners[b], corners[c]]);
+                }
+            }
+            "g" | "o" => {
+                group_name = if args.is_empty() {
+                    String::from("default")
+                } else {
+                    args.join(" ")
+                };
+            }
+            "usemtl" => {
+                let name = args.join(" ");
+                if !materials.contains_key(&name) {
+                    return Err(LoadError::parse(file, line, format!("unknown material '{}'", name)));
+                }
+                material_name = Some(name);
+            }
+            "mtllib" => {
+                for lib in args.iter() {
+                    materials.extend(load_mtl(lib)?);
+                }
+            }
+            _ => {} // s, l, p and friends don't affect triangle meshes
+        }
+    }
+
+    Ok(groups
+        .into_iter()
+        .filter(|group| !group.triangles.is_empty())
+        .map(|group| {
+            let material = match &group.material {
+                Some(name) => materials[name],
+                None => mtl::default_material(),
+            };
+            build_mesh(&group.triangles, &positions, &normals, &uvs, material)
+        })
+        .collect())
+}
+
+// Unifies (v, vt, vn) triples into shared vertices
+fn build_mesh(
+    triangles: &[[Corner; 3]],
+    positions: &[Point3],
+    normals: &[Vec3],
+    uvs: &[(f64, f64)],
+    material: Material,
+) -> TriangleMesh {
+    let corners = || triangles.iter().flatten();
+    let has_uvs = corners().all(|c| c.1.is_some());
+    let has_normals = corners().all(|c| c.2.is_some());
+
+    let mut vertex_lookup: HashMap<Corner, usize> = HashMap::new();
+    let mut mesh_positions = Vec::new();
+    let mut mesh_normals = Vec::new();
+    let mut mesh_uvs = Vec::new();
+    let mut indices = Vec::with_capacity(triangles.len());
+
+    for triangle in triangles {
+        let mut face = [0; 3];
+        for (slot, corner) in face.iter_mut().zip(triangle.iter()) {
+            let key = (
+                corner.0,
+                corner.1.filter(|_| has_uvs),
+                corner.2.filter(|_| has_normals),
+            );
+            *slot = *vertex_lookup.entry(key).or_insert_with(|| {
+                mesh_positions.push(positions[key.0]);
+                if let Some(vt) = key.1 {
+                    mesh_uvs.push(uvs[vt]);
+                }
+                if let Some(vn) = key.2 {
+                    mesh_normals.push(normals[vn]);
+                }
+                mesh_positions.len() - 1
+            });
+        }
+        indices.push(face);
+    }
+    TriangleMesh::new(mesh_positions, mesh_normals, mesh_uvs, indices, material)
+}
+
+pub fn load(path: &str) -> Result<Vec<TriangleMesh>, LoadError> {
+    let source = fs::read_to_string(path).map_err(|e| LoadError::io(path, e))?;
+    let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+    parse(&source, path, |lib| {
+        let lib_path = dir.join(lib);
+        mtl::load(&lib_path.to_string_lossy())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::math::ray::Ray;
+    use crate::primitives::Shadable;
+
+    const MTL: &str = "
+        newmtl red
+        Kd 1 0 0
+    ";
+
+    fn parse_str(source: &str) -> Result<Vec<TriangleMesh>, LoadError> {
+        parse(source, "test.obj", |lib| mtl::parse(MTL, lib))
+    }
+
+    #[test]
+    fn test_groups_and_materials() {
+        let meshes = parse_str(
+            "mtllib test.mtl
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            vn 0 0 1
+            g quad
+            usemtl red
+            f 1//1 2//1 3//1 4//1
+            g tri
+            f -4 -3 -2",
+        )
+        .unwrap();
+        assert_eq!(meshes.len(), 2);
+        assert_eq!(meshes[0].num_triangles(), 2);
+        assert_eq!(meshes[1].num_triangles(), 1);
+        assert!(matches!(meshes[0].material(), Material::Diffuse { .. }));
+
+        let ray = Ray::new(Point3::new(0.9, 0.1, 1.), Vec3::new(0., 0., -1.));
+        assert!(meshes[0].intersect(&ray).is_some());
+    }
+
+    #[test]
+    fn test_concave_polygon() {
+        // an L shape, fan triangulation from the first vertex would cover the notch
+        let meshes = parse_str(
+            "v 0 0 0
+            v 2 0 0
+            v 2 1 0
+            v 1 1 0
+            v 1 2 0
+            v 0 2 0
+            f 4 5 6 1 2 3",
+        )
+        .unwrap();
+        assert_eq!(meshes[0].num_triangles(), 4);
+        let notch = Ray::new(Point3::new(1.5, 1.5, 1.), Vec3::new(0., 0., -1.));
+        assert!(meshes[0].intersect(&notch).is_none());
+        let inside = Ray::new(Point3::new(0.5, 1.5, 1.), Vec3::new(0., 0., -1.));
+        assert!(meshes[0].intersect(&inside).is_some());
+    }
+
+    #[test]
+    fn test_errors_have_lines() {
+        let err = parse_str("v 0 0 0\nv 1 0 0\nf 1 2 3\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "test.obj:3: index 3 out of range, only 2 elements defined"
+        );
+        let err = parse_str("usemtl missing").unwrap_err();
+        assert_eq!(err.to_string(), "test.obj:1: unknown material 'missing'");
+        let err = parse_str("v 0 0 0\nv 1 0 0\nv 1 1 0\nv  nan 1 0\nf 1 2 3 4\n").unwrap_err();
+        assert_eq!(err.to_string(), "test.obj:4:4: 'nan' is not a finite number");
+        let err = parse_str("vn 0 1 1x").unwrap_err();
+        assert_eq!(err.to_string(), "test.obj:1:8: invalid number '1x'");
+    }
+}
@@ -0,0 +1,707 @@
+// Line based scene description, one statement per line and '#' starts a comment.
+// Statements take positional arguments followed by key=value options:
+//
+//   color SKY 0.5,0.7,1
+//   material paint diffuse color=RED
+//   material glaze mixed diffuse=RED specular=WHITE factor=0.2
+//   material gem refractive specular=WHITE refraction=0.9,1,0.9 ior=2.4 priority=1
+//   camera perspective origin=0,0,1 target=0,0,0 up=0,1,0 fov=60 fstop=2.8
+//   resolution 640 480
+//   sphere center=0,0,-0.5 radius=0.25 material=paint
+//   plane point=0,-0.25,0 normal=0,1,0 material=GLASS
+//   mesh file=models/teapot.obj
+//   light point position=2,5,0 color=WHITE power=1200
+//   light rect corner=-1,2,-1 u=2,0,0 v=0,0,2 brightness=5 samples=16
+//   light disk center=0,2,0 normal=0,-1,0 radius=0.5
+//   light sphere center=0,3,0 radius=0.25
+//   light directional direction=1,-2,0.5 angle=0.53 brightness=3
+//   light spot position=0,3,0 target=0,0,0 inner=15 outer=25 lumens=800
+//   background BLACK
+//   samples 16
+//   filter mitchell radius=2
+//   tone_map aces
+//   threads 8
+//   tile_size 32
+//
+// Light brightness is radiant intensity in W/sr for point and spot lights, radiance
+// for area lights and irradiance in W/m^2 for directional ones. Point, spot and area
+// lights can give their total output as power= in watts or lumens= instead.
+//
+// Colors are a name, a single grey value or r,g,b. The names in predef::colors and
+// predef::materials are always defined.
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::film::filter::Filter;
+use crate::film::ToneMap;
+use crate::loader::{obj, LoadError};
+use crate::math::point3::Point3;
+use crate::math::vec3::Vec3;
+use crate::predef::{colors, materials};
+use crate::primitives::camera::{
+    CameraFrame, EquirectangularCamera, FisheyeCamera, FisheyeMapping, OrthographicCamera,
+    PerspectiveCamera,
+};
+use crate::primitives::material::{Color, Material};
+use crate::primitives::mesh::TriangleMesh;
+use crate::primitives::light::{LUMENS_PER_WATT, DirectionalLight, DiskLight, RectLight, SphereLight, SpotLight};
+use crate::primitives::{Plane, PointLight, Sphere};
+use crate::scene::Scene;
+
+// A word of the line and the 1-based column it starts at
+#[derive(Debug, Copy, Clone)]
+struct Token<'s> {
+    text: &'s str,
+    column: usize,
+}
+
+fn tokenize(line: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut column = 0;
+    for (i, c) in line.char_indices() {
+        column += 1;
+        match (c.is_whitespace(), start) {
+            (true, Some((s, col))) => {
+                tokens.push(Token { text: &line[s..i], column: col });
+                start = None;
+            }
+            (false, None) => start = Some((i, column)),
+            _ => {}
+        }
+    }
+    if let Some((s, col)) = start {
+        tokens.push(Token { text: &line[s..], column: col });
+    }
+    tokens
+}
+
+#[derive(Debug)]
+struct Statement<'s> {
+    file: &'s str,
+    line: usize,
+    keyword: Token<'s>,
+    args: Vec<Token<'s>>,
+    options: Vec<(Token<'s>, Token<'s>)>,
+}
+
+impl<'s> Statement<'s> {
+    // None for blank lines
+    fn parse(file: &'s str, line: usize, content: &'s str) -> Result<Option<Statement<'s>>, LoadError> {
+        let mut tokens = tokenize(content).into_iter();
+        let keyword = match tokens.next() {
+            Some(k) => k,
+            None => return Ok(None),
+        };
+        let mut statement = Statement {
+            file,
+            line,
+            keyword,
+            args: Vec::new(),
+            options: Vec::new(),
+        };
+        for token in tokens {
+            match token.text.find('=') {
+                None if statement.options.is_empty() => statement.args.push(token),
+                None => return Err(statement.error(token, "arguments must come before options")),
+                Some(split) => {
+                    let (key, value) = (&token.text[..split], &token.text[split + 1..]);
+                    if key.is_empty() || value.is_empty() {
+                        return Err(statement.error(token, format!("malformed option '{}'", token.text)));
+                    }
+                    let value_column = token.column + key.chars().count() + 1;
+                    statement.options.push((
+                        Token { text: key, column: token.column },
+                        Token { text: value, column: value_column },
+                    ));
+                }
+            }
+        }
+        Ok(Some(statement))
+    }
+
+    fn error<S: Into<String>>(&self, token: Token, message: S) -> LoadError {
+        LoadError::parse_at(self.file, self.line, token.column, message)
+    }
+
+    // Exactly `count` positional arguments and only the listed options
+    fn expect(&self, count: usize, options: &[&str]) -> Result<(), LoadError> {
+        if let Some(extra) = self.args.get(count) {
+            return Err(self.error(*extra, format!("unexpected argument '{}'", extra.text)));
+        }
+        if self.args.len() < count {
+            return Err(self.error(
+                self.keyword,
+                format!("'{}' expects {} arguments, found {}", self.keyword.text, count, self.args.len()),
+            ));
+        }
+        for (i, (key, _)) in self.options.iter().enumerate() {
+            if !options.contains(&key.text) {
+                return Err(self.error(*key, format!("unknown option '{}' for '{}'", key.text, self.keyword.text)));
+            }
+            if self.options[..i].iter().any(|(other, _)| other.text == key.text) {
+                return Err(self.error(*key, format!("option '{}' given twice", key.text)));
+            }
+        }
+        Ok(())
+    }
+
+    fn option(&self, key: &str) -> Option<Token<'s>> {
+        self.options
+            .iter()
+            .find(|(k, _)| k.text == key)
+            .map(|(_, value)| *value)
+    }
+
+    fn required(&self, key: &str) -> Result<Token<'s>, LoadError> {
+        self.option(key)
+            .ok_or_else(|| self.error(self.keyword, format!("'{}' needs a {}= option", self.keyword.text, key)))
+    }
+
+    fn number(&self, token: Token) -> Result<f64, LoadError> {
+        token
+            .text
+            .parse()
+            .map_err(|_| self.error(token, format!("invalid number '{}'", token.text)))
+    }
+
+    fn integer(&self, token: Token) -> Result<u32, LoadError> {
+        token
+            .text
+            .parse()
+            .map_err(|_| self.error(token, format!("invalid integer '{}'", token.text)))
+    }
+
+    fn numbers(&self, token: Token) -> Result<Vec<f64>, LoadError> {
+        token
+            .text
+            .split(',')
+            .map(|part| {
+                part.parse()
+                    .map_err(|_| self.error(token, format!("invalid number '{}' in '{}'", part, token.text)))
+            })
+            .collect()
+    }
+
+    fn vector(&self, token: Token) -> Result<Vec3, LoadError> {
+        match self.numbers(token)?.as_slice() {
+            [x, y, z] => Ok(Vec3::new(*x, *y, *z)),
+            _ => Err(self.error(token, format!("expected x,y,z, found '{}'", token.text))),
+        }
+    }
+
+    fn number_or(&self, key: &str, default: f64) -> Result<f64, LoadError> {
+        self.option(key).map_or(Ok(default), |t| self.number(t))
+    }
+
+    fn non_negative(&self, key: &str, token: Token) -> Result<f64, LoadError> {
+        let value = self.number(token)?;
+        if value < 0. {
+            return Err(self.error(token, format!("{}= must not be negative", key)));
+        }
+        Ok(value)
+    }
+
+    fn non_negative_or(&self, key: &str, default: f64) -> Result<f64, LoadError> {
+        self.option(key).map_or(Ok(default), |t| self.non_negative(key, t))
+    }
+
+    fn positive(&self, key: &str) -> Result<f64, LoadError> {
+        let token = self.required(key)?;
+        let value = self.number(token)?;
+        if value <= 0. {
+            return Err(self.error(token, format!("{}= must be positive", key)));
+        }
+        Ok(value)
+    }
+
+    fn point_or(&self, key: &str, default: Point3) -> Result<Point3, LoadError> {
+        self.option(key)
+            .map_or(Ok(default), |t| self.vector(t).map(|v| v.as_point3()))
+    }
+
+    fn vector_or(&self, key: &str, default: Vec3) -> Result<Vec3, LoadError> {
+        self.option(key).map_or(Ok(default), |t| self.vector(t))
+    }
+}
+
+// Named colors and materials visible to later statements
+struct Definitions {
+    colors: HashMap<String, Color>,
+    materials: HashMap<String, Material>,
+}
+
+impl Definitions {
+    fn new() -> Definitions {
+        let colors = [
+            ("RED", colors::RED),
+            ("GREEN", colors::GREEN),
+            ("BLUE", colors::BLUE),
+            ("BLACK", colors::BLACK),
+            ("GREY", colors::GREY),
+            ("WHITE", colors::WHITE),
+        ];
+        let materials = [("GLASS", materials::GLASS), ("WATER", materials::WATER)];
+        Definitions {
+            colors: colors.iter().map(|(n, c)| (n.to_string(), *c)).collect(),
+            materials: materials.iter().map(|(n, m)| (n.to_string(), *m)).collect(),
+        }
+    }
+
+    fn color(&self, st: &Statement, token: Token) -> Result<Color, LoadError> {
+        if let Some(col) = self.colors.get(token.text) {
+            return Ok(*col);
+        }
+        let starts_numeric = token
+            .text
+            .starts_with(|c: char| c.is_ascii_digit() || c == '.' || c == '-');
+        if !starts_numeric {
+            return Err(st.error(token, format!("unknown color '{}'", token.text)));
+        }
+        match st.numbers(token)?.as_slice() {
+            [v] => Ok(Color::new(*v, *v, *v)),
+            [r, g, b] => Ok(Color::new(*r, *g, *b)),
+            _ => Err(st.error(token, format!("expected 1 or 3 color values, found '{}'", token.text))),
+        }
+    }
+
+    fn color_or(&self, st: &Statement, key: &str, default: Color) -> Result<Color, LoadError> {
+        st.option(key).map_or(Ok(default), |t| self.color(st, t))
+    }
+
+    fn material(&self, st: &Statement, token: Token) -> Result<Material, LoadError> {
+        self.materials
+            .get(token.text)
+            .copied()
+            .ok_or_else(|| st.error(token, format!("unknown material '{}'", token.text)))
+    }
+}
+
+fn parse_material(st: &Statement, defs: &Definitions) -> Result<Material, LoadError> {
+    let kind = st.args[1];
+    match kind.text {
+        "diffuse" => {
+            st.expect(2, &["color"])?;
+            Ok(Material::diffuse(defs.color(st, st.required("color")?)?))
+        }
+        "specular" => {
+            st.expect(2, &["color"])?;
+            Ok(Material::specular(defs.color(st, st.required("color")?)?))
+        }
+        "mixed" => {
+            st.expect(2, &["diffuse", "specular", "factor"])?;
+            let diffuse = defs.color(st, st.required("diffuse")?)?;
+            let specular = defs.color(st, st.required("specular")?)?;
+            let factor = st.number_or("factor", 0.5)?;
+            if !(0. ..=1.).contains(&factor) {
+                return Err(st.error(st.required("factor")?, "factor= must lie between 0 and 1"));
+            }
+            Ok(Material::mixed(diffuse, specular, factor))
+        }
+        "refractive" => {
+            st.expect(2, &["specular", "refraction", "ior", "priority"])?;
+            let specular = defs.color_or(st, "specular", colors::WHITE)?;
+            let refraction = defs.color_or(st, "refraction", colors::WHITE)?;
+            let priority = st.option("priority").map_or(Ok(0), |t| st.integer(t))?;
+            Ok(Material::refractive(specular, refraction, st.number_or("ior", 1.5)?).with_priority(priority))
+        }
+        other => Err(st.error(kind, format!("unknown material type '{}'", other))),
+    }
+}
+
+fn parse_frame(st: &Statement) -> Result<(CameraFrame, Point3), LoadError> {
+    let origin = st.point_or("origin", Point3::new(0., 0., 0.))?;
+    let target = st.point_or("target", Point3::new(0., 0., -1.))?;
+    let up = st.vector_or("up", Vec3::new(0., 1., 0.))?;
+    if target.sub(&origin).square() == 0. {
+        return Err(st.error(st.keyword, "camera origin and target are the same point"));
+    }
+    let mut frame = CameraFrame::look_at(&origin, &target, &up);
+    frame.set_roll(st.number_or("roll", 0.)?);
+    Ok((frame, target))
+}
+
+fn parse_camera(st: &Statement, scene: &mut Scene) -> Result<(), LoadError> {
+    const FRAME: [&str; 4] = ["origin", "target", "up", "roll"];
+    let with_frame = |extra: &[&'static str]| -> Vec<&'static str> { FRAME.iter().chain(extra).copied().collect() };
+    let (res_x, res_y) = scene.camera().resolution();
+
+    let kind = st.args[0];
+    match kind.text {
+        "perspective" => {
+            st.expect(1, &with_frame(&["fov", "fstop", "focal_length", "focus_distance"]))?;
+            let (frame, target) = parse_frame(st)?;
+            let mut camera = PerspectiveCamera::default();
+            camera.frame = frame;
+            camera.set_resolution(res_x, res_y).focus_on(&target);
+            if let Some(fov) = st.option("fov") {
+                camera.set_fov(st.number(fov)?);
+            }
+            if let Some(length) = st.option("focal_length") {
+                camera.set_focal_length(st.number(length)?);
+            }
+            if let Some(fstop) = st.option("fstop") {
+                camera.set_fstop(st.number(fstop)?);
+            }
+            if let Some(dist) = st.option("focus_distance") {
+                camera.set_focus_distance(st.number(dist)?);
+            }
+            scene.set_camera(camera);
+        }
+        "orthographic" => {
+            st.expect(1, &with_frame(&["width"]))?;
+            let (frame, _) = parse_frame(st)?;
+            scene.set_camera(OrthographicCamera::new(frame, st.number_or("width", 4.)?, res_x, res_y));
+        }
+        "fisheye" => {
+            st.expect(1, &with_frame(&["fov", "mapping"]))?;
+            let (frame, _) = parse_frame(st)?;
+            let mapping = match st.option("mapping") {
+                None => FisheyeMapping::Equidistant,
+                Some(t) => match t.text {
+                    "equidistant" => FisheyeMapping::Equidistant,
+                    "equisolid" => FisheyeMapping::Equisolid,
+                    other => return Err(st.error(t, format!("unknown fisheye mapping '{}'", other))),
+                },
+            };
+            let fov = st.number_or("fov", 180.)?;
+            scene.set_camera(FisheyeCamera::new(frame, mapping, fov, res_x, res_y));
+        }
+        "equirectangular" => {
+            st.expect(1, &FRAME)?;
+            let (frame, _) = parse_frame(st)?;
+            scene.set_camera(EquirectangularCamera::new(frame, res_x, res_y));
+        }
+        other => return Err(st.error(kind, format!("unknown camera type '{}'", other))),
+    }
+    Ok(())
+}
+
+fn parse_filter(st: &Statement) -> Result<Filter, LoadError> {
+    let kind = st.args[0];
+    let filter = match kind.text {
+        "box" => {
+            st.expect(1, &["radius"])?;
+            Filter::Box { radius: st.number_or("radius", 0.5)? }
+        }
+        "tent" => {
+            st.expect(1, &["radius"])?;
+            Filter::Tent { radius: st.number_or("radius", 1.)? }
+        }
+        "gaussian" => {
+            st.expect(1, &["radius", "alpha"])?;
+            Filter::Gaussian {
+                radius: st.number_or("radius", 1.5)?,
+                alpha: st.number_or("alpha", 2.)?,
+            }
+        }
+        "mitchell" => {
+            st.expect(1, &["radius", "b", "c"])?;
+            Filter::Mitchell {
+                radius: st.number_or("radius", 2.)?,
+                b: st.number_or("b", 1. / 3.)?,
+                c: st.number_or("c", 1. / 3.)?,
+            }
+        }
+        "lanczos" => {
+            st.expect(1, &["radius"])?;
+            Filter::Lanczos { radius: st.number_or("radius", 3.)? }
+        }
+        other => return Err(st.error(kind, format!("unknown filter '{}'", other))),
+    };
+    if filter.radius() <= 0. {
+        return Err(st.error(st.keyword, "filter radius must be positive"));
+    }
+    Ok(filter)
+}
+
+fn parse_tone_map(st: &Statement) -> Result<ToneMap, LoadError> {
+    let kind = st.args[0];
+    match kind.text {
+        "linear" => {
+            st.expect(1, &["exposure"])?;
+            Ok(ToneMap::Linear { exposure: st.number_or("exposure", 0.)? })
+        }
+        "reinhard" => {
+            st.expect(1, &[])?;
+            Ok(ToneMap::Reinhard)
+        }
+        "extended_reinhard" => {
+            st.expect(1, &["white_point"])?;
+            Ok(ToneMap::ExtendedReinhard { white_point: st.number_or("white_point", 4.)? })
+        }
+        "aces" => {
+            st.expect(1, &[])?;
+            Ok(ToneMap::AcesFilmic)
+        }
+        other => Err(st.error(kind, format!("unknown tone map '{}'", other))),
+    }
+}
+
+fn parse_light(st: &Statement, defs: &Definitions, scene: &mut Scene) -> Result<(), LoadError> {
+    const EMISSION: [&str; 5] = ["color", "brightness", "power", "lumens", "samples"];
+    let with_emission = |extra: &[&'static str]| -> Vec<&'static str> { extra.iter().chain(&EMISSION).copied().collect() };
+    let col = defs.color_or(st, "color", colors::WHITE)?;
+    let brightness = st.non_negative_or("brightness", 1.)?;
+    // total emitted power overrides brightness, in watts or converted from lumens
+    let power = match (st.option("power"), st.option("lumens")) {
+        (Some(_), Some(lumens)) => return Err(st.error(lumens, "give either power= or lumens=, not both")),
+        (Some(watts), None) => Some(st.non_negative("power", watts)?),
+        (None, Some(lumens)) => Some(st.non_negative("lumens", lumens)? / LUMENS_PER_WATT),
+        (None, None) => None,
+    };
+    let samples = match st.option("samples") {
+        Some(t) if st.integer(t)? == 0 => return Err(st.error(t, "samples= must be at least 1")),
+        Some(t) => Some(st.integer(t)?),
+        None => None,
+    };
+
+    let kind = st.args[0];
+    match kind.text {
+        "point" => {
+            st.expect(1, &["position", "color", "brightness", "power", "lumens"])?;
+            let position = st.point_or("position", Point3::new(0., 0., 0.))?;
+            let mut light = PointLight::new(position, col, brightness);
+            if let Some(watts) = power {
+                light.set_power(watts);
+            }
+            scene.add_light(light);
+        }
+        "rect" => {
+            st.expect(1, &with_emission(&["corner", "u", "v"]))?;
+            let corner = st.point_or("corner", Point3::new(0., 0., 0.))?;
+            let edge_u = st.vector(st.required("u")?)?;
+            let edge_v = st.vector(st.required("v")?)?;
+            let mut light = RectLight::new(corner, edge_u, edge_v, col, brightness);
+            light.samples = samples.unwrap_or(light.samples);
+            if let Some(watts) = power {
+                light.set_power(watts);
+            }
+            scene.add_light(light);
+        }
+        "disk" => {
+            st.expect(1, &with_emission(&["center", "normal", "radius"]))?;
+            let center = st.point_or("center", Point3::new(0., 0., 0.))?;
+            let normal = st.vector_or("normal", Vec3::new(0., -1., 0.))?;
+            let radius = st.positive("radius")?;
+            let mut light = DiskLight::new(center, normal, radius, col, brightness);
+            light.samples = samples.unwrap_or(light.samples);
+            if let Some(watts) = power {
+                light.set_power(watts);
+            }
+            scene.add_light(light);
+        }
+        "sphere" => {
+            st.expect(1, &with_emission(&["center", "radius"]))?;
+            let center = st.point_or("center", Point3::new(0., 0., 0.))?;
+            let radius = st.positive("radius")?;
+            let mut light = SphereLight::new(center, radius, col, brightness);
+            light.samples = samples.unwrap_or(light.samples);
+            if let Some(watts) = power {
+                light.set_power(watts);
+            }
+            scene.add_light(light);
+        }
+        "directional" => {
+            // brightness is irradiance here, there is no total power for a light at infinity
+            st.expect(1, &["direction", "angle", "color", "brightness", "samples"])?;
+            let direction = st.vector_or("direction", Vec3::new(0., -1., 0.))?;
+            let angle = st.number_or("angle", 0.)?;
+            let mut light = DirectionalLight::new(direction, col, brightness).with_angular_diameter(angle);
+            light.samples = samples.unwrap_or(light.samples);
+            scene.add_light(light);
+        }
+        "spot" => {
+            st.expect(1, &["position", "direction", "target", "inner", "outer", "color", "brightness", "power", "lumens"])?;
+            let position = st.point_or("position", Point3::new(0., 0., 0.))?;
+            let direction = match st.option("target") {
+                Some(t) => st.vector(t)?.as_point3().sub(&position),
+                None => st.vector_or("direction", Vec3::new(0., -1., 0.))?,
+            };
+            let mut light = SpotLight::new(position, direction, col, brightness);
+            let (inner, outer) = (light.inner_angle, light.outer_angle);
+            light.set_cone(st.number_or("inner", inner)?, st.number_or("outer", outer)?);
+            if let Some(watts) = power {
+                light.set_power(watts);
+            }
+            scene.add_light(light);
+        }
+        other => return Err(st.error(kind, format!("unknown light type '{}'", other))),
+    }
+    Ok(())
+}
+
+// `load_mesh` resolves the files named by mesh statements
+pub fn parse<F>(source: &str, file: &str, mut load_mesh: F) -> Result<Scene<'static>, LoadError>
+where
+    F: FnMut(&str) -> Result<Vec<TriangleMesh>, LoadError>,
+{
+    let mut scene = Scene::new();
+    let mut defs = Definitions::new();
+
+    for (i, raw_line) in source.lines().enumerate() {
+        let content = raw_line.split('#').next().unwrap_or("");
+        let st = match Statement::parse(file, i + 1, content)? {
+            Some(st) => st,
+            None => continue,
+        };
+        // every statement but these names its type or value first
+        let needs_arg = !matches!(st.keyword.text, "sphere" | "plane" | "mesh");
+        if needs_arg && st.args.is_empty() {
+            return Err(st.error(st.keyword, format!("'{}' needs an argument", st.keyword.text)));
+        }
+
+        match st.keyword.text {
+            "color" => {
+                st.expect(2, &[])?;
+                let col = defs.color(&st, st.args[1])?;
+                defs.colors.insert(st.args[0].text.to_string(), col);
+            }
+            "material" => {
+                if st.args.len() < 2 {
+                    return Err(st.error(st.keyword, "'material' needs a name and a type"));
+                }
+                let material = parse_material(&st, &defs)?;
+                defs.materials.insert(st.args[0].text.to_string(), material);
+            }
+            "camera" => parse_camera(&st, &mut scene)?,
+            "resolution" => {
+                st.expect(2, &[])?;
+                let (x, y) = (st.integer(st.args[0])?, st.integer(st.args[1])?);
+                if x == 0 || y == 0 {
+                    return Err(st.error(st.args[0], "resolution must be at least 1x1"));
+                }
+                scene.camera_mut().set_resolution(x, y);
+            }
+            "sphere" => {
+                st.expect(0, &["center", "radius", "material"])?;
+                let center = st.point_or("center", Point3::new(0., 0., 0.))?;
+                let radius = st.positive("radius")?;
+                let material = defs.material(&st, st.required("material")?)?;
+                scene.add_object(Sphere::new(center, radius, material));
+            }
+            "plane" => {
+                st.expect(0, &["point", "normal", "material"])?;
+                let point = st.point_or("point", Point3::new(0., 0., 0.))?;
+                let normal = st.vector_or("normal", Vec3::new(0., 1., 0.))?;
+                let material = defs.material(&st, st.required("material")?)?;
+                scene.add_object(Plane::new(point, normal.norm(), material));
+            }
+            "mesh" => {
+                st.expect(0, &["file"])?;
+                for mesh in load_mesh(st.required("file")?.text)? {
+                    scene.add_object(mesh);
+                }
+            }
+            "light" => parse_light(&st, &defs, &mut scene)?,
+            "background" => {
+                st.expect(1, &[])?;
+                scene.set_background_col(defs.color(&st, st.args[0])?);
+            }
+            "samples" => {
+                st.expect(1, &[])?;
+                scene.settings_mut().samples_per_pixel = st.integer(st.args[0])?.max(1);
+            }
+            "filter" => scene.settings_mut().filter = parse_filter(&st)?,
+            "tone_map" => scene.settings_mut().tone_map = parse_tone_map(&st)?,
+            "threads" => {
+                st.expect(1, &[])?;
+                scene.settings_mut().threads = st.integer(st.args[0])? as usize;
+            }
+            "tile_size" => {
+                st.expect(1, &[])?;
+                scene.settings_mut().tile_size = st.integer(st.args[0])?.max(1);
+            }
+            other => return Err(st.error(st.keyword, format!("unknown statement '{}'", other))),
+        }
+    }
+    Ok(scene)
+}
+
+pub fn load(path: &str) -> Result<Scene<'static>, LoadError> {
+    let source = fs::read_to_string(path).map_err(|e| LoadError::io(path, e))?;
+    let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+    parse(&source, path, |mesh| {
+        let mesh_path = dir.join(mesh);
+        obj::load(&mesh_path.to_string_lossy())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse_str(source: &str) -> Result<Scene<'static>, LoadError> {
+        parse(source, "test.scene", |_| Ok(Vec::new()))
+    }
+
+    fn error_position(source: &str) -> (usize, Option<usize>) {
+        match parse_str(source) {
+            Err(LoadError::Parse { line, column, .. }) => (line, column),
+            other => panic!("expected a parse error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_parse_scene() {
+        let source = "
+            # two spheres on a floor
+            color SKY 0.5,0.7,1
+            material paint diffuse color=RED
+            material glaze mixed diffuse=SKY specular=WHITE factor=0.2
+            camera perspective origin=0,0,1 target=0,0,0 fov=60
+            resolution 32 24
+            sphere center=0,0,-0.5 radius=0.25 material=paint
+            sphere radius=0.1 material=GLASS
+            plane point=0,-0.25,0 normal=0,2,0 material=glaze
+            light point position=2,5,0 brightness=100
+            light rect corner=-1,2,-1 u=2,0,0 v=0,0,2 samples=4
+            light disk center=0,2,0 radius=0.5
+            light sphere center=0,3,0 radius=0.25 color=SKY
+            light directional direction=1,-2,0 angle=0.5
+            light spot position=0,3,0 target=0,0,0 outer=25
+            background SKY
+            samples 4
+            filter gaussian alpha=3
+            tone_map extended_reinhard white_point=2
+            threads 2
+        ";
+        let scene = parse_str(source).unwrap();
+        assert_eq!(scene.camera().resolution(), (32, 24));
+        assert_eq!(scene.lights().len(), 6);
+        assert_eq!(scene.lights()[1].samples(), 4);
+        assert_eq!(scene.background_col().blue, 1.);
+        let settings = scene.settings();
+        assert_eq!(settings.samples_per_pixel, 4);
+        assert_eq!(settings.threads, 2);
+        assert_eq!(settings.filter.radius(), 1.5);
+        match settings.tone_map {
+            ToneMap::ExtendedReinhard { white_point } => assert_eq!(white_point, 2.),
+            other => panic!("wrong tone map {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_error_columns() {
+        assert_eq!(error_position("sphere radius=1 material=PAINT"), (1, Some(26)));
+        assert_eq!(error_position("\n  camera perspective fov=6O"), (2, Some(26)));
+        assert_eq!(error_position("light point position=1,2"), (1, Some(22)));
+        assert_eq!(error_position("sphere material=GLASS"), (1, Some(1)));
+        assert_eq!(error_position("samples 4 colour=RED"), (1, Some(11)));
+        assert_eq!(error_position("shpere radius=1"), (1, Some(1)));
+        assert_eq!(error_position("light disk center=0,1,0"), (1, Some(1)));
+        assert_eq!(error_position("light point samples=4"), (1, Some(13)));
+        assert_eq!(error_position("light point brightness=-5"), (1, Some(24)));
+        assert_eq!(error_position("light rect u=1,0,0 v=0,0,1 power=-1"), (1, Some(34)));
+        assert_eq!(error_position("light spot lumens=-800"), (1, Some(19)));
+        assert_eq!(error_position("light sphere radius=-1"), (1, Some(21)));
+        assert_eq!(error_position("light disk radius=1 samples=-4"), (1, Some(29)));
+        assert_eq!(error_position("light disk radius=1 samples=0"), (1, Some(29)));
+        assert_eq!(error_position("sphere radius=0 material=GLASS"), (1, Some(15)));
+        assert_eq!(error_position("material m mixed diffuse=RED specular=WHITE factor=1.5"), (1, Some(52)));
+
+        let message = parse_str("resolution 64 x").map(|_| ()).unwrap_err().to_string();
+        assert_eq!(message, "test.scene:1:15: invalid integer 'x'");
+    }
+}
@@ -1,47 +1,246 @@
-//use std::fs::File;
-//use std::io;
-//use std::io::prelude::*;
-use ton::math::point3::Point3;
-use ton::math::vec3::Vec3;
-use ton::primitives::camera::Camera;
-use ton::primitives::material::{Material,Color};
-use ton::predef::colors;
-use ton::primitives::{PointLight,Sphere,Plane};
-use ton::scene::Scene;
+use std::env;
+use std::fmt;
+use std::path::Path;
+use std::process;
 
-fn main() {
-    let red_refractive = Material::refractive(colors::WHITE,colors::WHITE,1.5);
-    let red_diffuse = Material::diffuse(colors::RED);
-    let _red_specular = Material::specular(colors::RED);
-    let blue_diffuse = Material::diffuse(colors::BLUE);
-    let grey_diffuse = Material::diffuse(Color::new(0.25,0.25,0.25));
+use image::ImageFormat;
+
+use ton::integrator::{
+    AoIntegrator, DepthIntegrator, Integrator, NormalIntegrator, PathIntegrator, WhittedIntegrator,
+};
+use ton::loader::scene;
+use ton::scene::Crop;
+
+const USAGE: &str = "usage: ton [options] <scene>
+
+options:
+  -o, --output <file>       image to write, default output.png
+  -f, --format <format>     png, jpeg, bmp, tga or pnm, default from the output extension
+  -r, --resolution <WxH>    override the camera resolution
+  -s, --spp <n>             samples per pixel
+  -d, --max-depth <n>       maximum bounces for the whitted and path integrators
+  -t, --threads <n>         worker threads, 0 uses every core
+  -i, --integrator <name>   whitted, path, ao, normal or depth, default whitted
+  -c, --crop <x0,y0,x1,y1>  only render pixels [x0, x1) x [y0, y1)
+  -h, --help                print this message";
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    scene: String,
+    output: String,
+    format: Option<ImageFormat>,
+    resolution: Option<(u32, u32)>,
+    spp: Option<u32>,
+    max_depth: Option<u32>,
+    threads: Option<usize>,
+    integrator: String,
+    crop: Option<Crop>,
+}
+
+#[derive(Debug, PartialEq)]
+enum CliError {
+    Help,
+    Usage(String),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::Help => write!(f, "{}", USAGE),
+            CliError::Usage(message) => write!(f, "{}\n\n{}", message, USAGE),
+        }
+    }
+}
+
+fn usage<S: Into<String>>(message: S) -> CliError {
+    CliError::Usage(message.into())
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, CliError> {
+    value
+        .parse()
+        .map_err(|_| usage(format!("invalid value '{}' for {}", value, flag)))
+}
 
-    let mut scene = Scene::new();
-    let sphere_one   = Sphere::new(Point3::new(0., 0., -0.5), 0.25, red_diffuse);
-    let sphere_two   = Sphere::new(Point3::new(-0.3, 0.0, 0.2), 0.25, blue_diffuse);
-    let sphere_three = Sphere::new(Point3::new(0.2, 0., 0.1), 0.25, red_refractive);
+fn parse_format(name: &str) -> Option<ImageFormat> {
+    match name.to_lowercase().as_str() {
+        "png" => Some(ImageFormat::PNG),
+        "jpg" | "jpeg" => Some(ImageFormat::JPEG),
+        "bmp" => Some(ImageFormat::BMP),
+        "tga" => Some(ImageFormat::TGA),
+        "pnm" | "ppm" => Some(ImageFormat::PNM),
+        _ => None,
+    }
+}
 
-    let plane_one = Plane::new(Point3::new(0.,-0.25,0.),Vec3::new(0.,1.,0.),grey_diffuse);
+fn parse_args<I: Iterator<Item = String>>(mut args: I) -> Result<Options, CliError> {
+    let mut scene = None;
+    let mut options = Options {
+        scene: String::new(),
+        output: String::from("output.png"),
+        format: None,
+        resolution: None,
+        spp: None,
+        max_depth: None,
+        threads: None,
+        integrator: String::from("whitted"),
+        crop: None,
+    };
 
-    let mut camera = Camera::default();
-    camera.set_origin(Point3::new(0., 0., 1.))
-          .point_at(&Point3::new(0., 0., 0.));
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            return Err(CliError::Help);
+        }
+        if !arg.starts_with('-') {
+            if scene.replace(arg.clone()).is_some() {
+                return Err(usage(format!("unexpected argument '{}'", arg)));
+            }
+            continue;
+        }
+        const FLAGS: [&str; 16] = [
+            "-o", "--output", "-f", "--format", "-r", "--resolution", "-s", "--spp",
+            "-d", "--max-depth", "-t", "--threads", "-i", "--integrator", "-c", "--crop",
+        ];
+        if !FLAGS.contains(&arg.as_str()) {
+            return Err(usage(format!("unknown option '{}'", arg)));
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| usage(format!("{} needs a value", arg)))?;
+        match arg.as_str() {
+            "-o" | "--output" => options.output = value,
+            "-f" | "--format" => {
+                let format = parse_format(&value)
+                    .ok_or_else(|| usage(format!("unknown image format '{}'", value)))?;
+                options.format = Some(format);
+            }
+            "-r" | "--resolution" => {
+                let (x, y) = value
+                    .split_once('x')
+                    .ok_or_else(|| usage(format!("resolution '{}' should look like 640x480", value)))?;
+                let resolution = (parse_number(&arg, x)?, parse_number(&arg, y)?);
+                if resolution.0 == 0 || resolution.1 == 0 {
+                    return Err(usage("resolution must be at least 1x1"));
+                }
+                options.resolution = Some(resolution);
+            }
+            "-s" | "--spp" => options.spp = Some(parse_number(&arg, &value)?),
+            "-d" | "--max-depth" => options.max_depth = Some(parse_number(&arg, &value)?),
+            "-t" | "--threads" => options.threads = Some(parse_number(&arg, &value)?),
+            "-i" | "--integrator" => options.integrator = value,
+            "-c" | "--crop" => {
+                let bounds = value
+                    .split(',')
+                    .map(|v| parse_number(&arg, v))
+                    .collect::<Result<Vec<u32>, CliError>>()?;
+                match bounds.as_slice() {
+                    [x0, y0, x1, y1] if x0 < x1 && y0 < y1 => {
+                        options.crop = Some(Crop {
+                            x0: *x0,
+                            y0: *y0,
+                            x1: *x1,
+                            y1: *y1,
+                        })
+                    }
+                    _ => return Err(usage(format!("crop '{}' should be x0,y0,x1,y1 with x0 < x1 and y0 < y1", value))),
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
 
-    let mut light = PointLight::default();
+    options.scene = scene.ok_or_else(|| usage("no scene file given"))?;
+    Ok(options)
+}
 
-    light.move_to(&Point3::new(2., 5., 0.));
+fn make_integrator(options: &Options) -> Result<Box<dyn Integrator>, String> {
+    let depth = options.max_depth;
+    Ok(match options.integrator.as_str() {
+        "whitted" => Box::new(depth.map_or_else(WhittedIntegrator::default, WhittedIntegrator::new)),
+        "path" => Box::new(depth.map_or_else(PathIntegrator::default, PathIntegrator::new)),
+        "ao" => Box::new(AoIntegrator::default()),
+        "normal" => Box::new(NormalIntegrator),
+        "depth" => Box::new(DepthIntegrator::default()),
+        other => return Err(format!("unknown integrator '{}'", other)),
+    })
+}
 
-    scene.set_camera(camera);
+fn run(options: &Options) -> Result<(), String> {
+    let format = match options.format {
+        Some(format) => format,
+        None => Path::new(&options.output)
+            .extension()
+            .and_then(|ext| parse_format(&ext.to_string_lossy()))
+            .ok_or_else(|| format!("can't tell the image format of '{}', use --format", options.output))?,
+    };
+    let integrator = make_integrator(options)?;
 
-    scene.add_object(sphere_one);
-    //scene.add_object(sphere_two);
-    scene.add_object(sphere_three);
+    let mut scene = scene::load(&options.scene).map_err(|e| e.to_string())?;
+    if let Some((x, y)) = options.resolution {
+        scene.camera_mut().set_resolution(x, y);
+    }
+    let settings = scene.settings_mut();
+    if let Some(spp) = options.spp {
+        settings.samples_per_pixel = spp.max(1);
+    }
+    if let Some(threads) = options.threads {
+        settings.threads = threads;
+    }
+    if let Some(crop) = options.crop {
+        let (res_x, res_y) = scene.camera().resolution();
+        if crop.x1 > res_x || crop.y1 > res_y {
+            return Err(format!("crop region is outside the {}x{} image", res_x, res_y));
+        }
+        scene.settings_mut().crop = Some(crop);
+    }
+
+    let image = scene.render(integrator.as_ref());
+    image
+        .save_with_format(&options.output, format)
+        .map_err(|e| format!("{}: {}", options.output, e))
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(CliError::Help) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(err) => {
+            eprintln!("ton: {}", err);
+            process::exit(2);
+        }
+    };
+    if let Err(message) = run(&options) {
+        eprintln!("ton: {}", message);
+        process::exit(1);
+    }
+}
 
-    scene.add_object(plane_one);
+#[cfg(test)]
+mod test {
+    use super::*;
 
-    scene.add_light(light);
+    fn parse(args: &[&str]) -> Result<Options, CliError> {
+        parse_args(args.iter().map(|a| a.to_string()))
+    }
 
-    scene.set_background_col(colors::BLACK);
+    #[test]
+    fn test_parse_args() {
+        let options = parse(&["-o", "out.bmp", "scene.txt", "--resolution", "320x200", "-s", "16", "--crop", "0,10,20,30"]).unwrap();
+        assert_eq!(options.scene, "scene.txt");
+        assert_eq!(options.output, "out.bmp");
+        assert_eq!(options.resolution, Some((320, 200)));
+        assert_eq!(options.spp, Some(16));
+        assert_eq!(options.crop, Some(Crop { x0: 0, y0: 10, x1: 20, y1: 30 }));
 
-    scene.render().save("output/test.png").ok();
+        assert_eq!(parse(&["-h"]), Err(CliError::Help));
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["a.scene", "b.scene"]).is_err());
+        assert!(parse(&["a.scene", "--spp"]).is_err());
+        assert!(parse(&["a.scene", "--crop", "5,5,5,10"]).is_err());
+        assert!(parse(&["a.scene", "--resolution", "640"]).is_err());
+        assert_eq!(parse(&["a.scene", "--bogus"]), Err(usage("unknown option '--bogus'")));
+    }
 }
@@ -0,0 +1,137 @@
+use std::f64;
+
+use crate::math::mat4::Mat4;
+use crate::math::point3::Point3;
+use crate::math::ray::Ray;
+use crate::math::vec3::Vec3;
+
+// Axis-aligned bounding box
+#[derive(Debug, Copy, Clone)]
+pub struct Aabb {
+    pub min: Point3,
+    pub max: Point3,
+}
+
+impl Aabb {
+    pub fn new(a: &Point3, b: &Point3) -> Aabb {
+        Aabb {
+            min: Point3::new(a.x().min(*b.x()), a.y().min(*b.y()), a.z().min(*b.z())),
+            max: Point3::new(a.x().max(*b.x()), a.y().max(*b.y()), a.z().max(*b.z())),
+        }
+    }
+
+    // Contains nothing, the identity for union
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: Point3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            max: Point3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        }
+    }
+
+    pub fn from_point(p: &Point3) -> Aabb {
+        Aabb { min: *p, max: *p }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Point3::new(
+                self.min.x().min(*other.min.x()),
+                self.min.y().min(*other.min.y()),
+                self.min.z().min(*other.min.z()),
+            ),
+            max: Point3::new(
+                self.max.x().max(*other.max.x()),
+                self.max.y().max(*other.max.y()),
+                self.max.z().max(*other.max.z()),
+            ),
+        }
+    }
+
+    pub fn union_point(&self, p: &Point3) -> Aabb {
+        self.union(&Aabb::from_point(p))
+    }
+
+    pub fn extent(&self) -> Vec3 {
+        self.max.sub(&self.min)
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        self.min.add(&self.extent().scale(0.5))
+    }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.extent();
+        if *d.x() < 0. || *d.y() < 0. || *d.z() < 0. {
+            return 0.;
+        }
+        2. * (d.x() * d.y() + d.y() * d.z() + d.z() * d.x())
+    }
+
+    pub fn max_axis(&self) -> usize {
+        let d = self.extent();
+        if d.x() > d.y() && d.x() > d.z() {
+            0
+        } else if d.y() > d.z() {
+            1
+        } else {
+            2
+        }
+    }
+
+    // Bounds of all eight transformed corners
+    pub fn transform(&self, m: &Mat4) -> Aabb {
+        let mut result = Aabb::empty();
+        for i in 0..8 {
+            let corner = Point3::new(
+                if i & 1 == 0 { *self.min.x() } else { *self.max.x() },
+                if i & 2 == 0 { *self.min.y() } else { *self.max.y() },
+                if i & 4 == 0 { *self.min.z() } else { *self.max.z() },
+            );
+            result = result.union_point(&m.transform_point(&corner));
+        }
+        result
+    }
+
+    // Slab test, returns the parametric entry and exit distances clipped to the ray's interval
+    pub fn intersect(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let mut t0 = ray.t_min;
+        let mut t1 = ray.t_max;
+        for axis in 0..3 {
+            let inv_dir = ray.direction().axis(axis).recip();
+            let origin = ray.origin().axis(axis);
+            let mut near = (self.min.axis(axis) - origin) * inv_dir;
+            let mut far = (self.max.axis(axis) - origin) * inv_dir;
+            if near > far {
+                std::mem::swap(&mut near, &mut far);
+            }
+            // NaN (0 * inf) compares false and leaves the interval untouched
+            if near > t0 {
+                t0 = near;
+            }
+            if far < t1 {
+                t1 = far;
+            }
+            if t0 > t1 {
+                return None;
+            }
+        }
+        Some((t0, t1))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ray_box() {
+        let aabb = Aabb::new(&Point3::new(-1., -1., -1.), &Point3::new(1., 1., 1.));
+        let hit = Ray::new(Point3::new(0., 0., 5.), Vec3::new(0., 0., -1.));
+        let miss = Ray::new(Point3::new(2., 0., 5.), Vec3::new(0., 0., -1.));
+        assert_eq!(aabb.intersect(&hit), Some((4., 6.)));
+        let mut short = hit.clone();
+        short.t_max = 3.;
+        assert_eq!(aabb.intersect(&short), None);
+        assert_eq!(aabb.intersect(&miss), None);
+    }
+}
@@ -1,11 +1,43 @@
+use crate::math::gamma;
+use crate::math::point3::Point3;
+use crate::math::vec3::Vec3;
+
+// Row-major 4x4 matrix, points and vectors are treated as column vectors (p' = M * p)
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Mat4 {
-    data: [i32; 16],
+    data: [f64; 16],
 }
 
 impl Mat4 {
     pub fn new() -> Mat4 {
-        Mat4 { data: [0; 16] }
+        Mat4 { data: [0.; 16] }
+    }
+
+    pub fn identity() -> Mat4 {
+        Mat4::from_rows([
+            [1., 0., 0., 0.],
+            [0., 1., 0., 0.],
+            [0., 0., 1., 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    pub fn from_rows(rows: [[f64; 4]; 4]) -> Mat4 {
+        let mut result = Mat4::new();
+        for (r, row) in rows.iter().enumerate() {
+            result.data[r * 4..r * 4 + 4].copy_from_slice(row);
+        }
+        result
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> f64 {
+        self.data[row * 4 + col]
     }
+
+    fn set(&mut self, row: usize, col: usize, val: f64) {
+        self.data[row * 4 + col] = val;
+    }
+
     pub fn add(&self, other: &Mat4) -> Mat4 {
         let mut result = Mat4::new();
         for i in 0..16 {
@@ -21,4 +53,277 @@ impl Mat4 {
         }
         result
     }
+
+    pub fn mul(&self, other: &Mat4) -> Mat4 {
+        let mut result = Mat4::new();
+        for r in 0..4 {
+            for c in 0..4 {
+                let mut sum = 0.;
+                for k in 0..4 {
+                    sum += self.get(r, k) * other.get(k, c);
+                }
+                result.set(r, c, sum);
+            }
+        }
+        result
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut result = Mat4::new();
+        for r in 0..4 {
+            for c in 0..4 {
+                result.set(c, r, self.get(r, c));
+            }
+        }
+        result
+    }
+
+    pub fn determinant(&self) -> f64 {
+        let m = &self.data;
+        // expand along the first row using 2x2 minors of the bottom two rows
+        let s0 = m[8] * m[13] - m[9] * m[12];
+        let s1 = m[8] * m[14] - m[10] * m[12];
+        let s2 = m[8] * m[15] - m[11] * m[12];
+        let s3 = m[9] * m[14] - m[10] * m[13];
+        let s4 = m[9] * m[15] - m[11] * m[13];
+        let s5 = m[10] * m[15] - m[11] * m[14];
+
+        m[0] * (m[5] * s5 - m[6] * s4 + m[7] * s3)
+            - m[1] * (m[4] * s5 - m[6] * s2 + m[7] * s1)
+            + m[2] * (m[4] * s4 - m[5] * s2 + m[7] * s0)
+            - m[3] * (m[4] * s3 - m[5] * s1 + m[6] * s0)
+    }
+
+    // Gauss-Jordan elimination with partial pivoting, None if the matrix is singular
+    pub fn inverse(&self) -> Option<Mat4> {
+        let mut m = *self;
+        let mut inv = Mat4::identity();
+
+        for col in 0..4 {
+            let mut pivot = col;
+            for row in col + 1..4 {
+                if m.get(row, col).abs() > m.get(pivot, col).abs() {
+                    pivot = row;
+                }
+            }
+            if m.get(pivot, col).abs() < 1e-12 {
+                return None;
+            }
+            if pivot != col {
+                for c in 0..4 {
+                    m.data.swap(pivot * 4 + c, col * 4 + c);
+                    inv.data.swap(pivot * 4 + c, col * 4 + c);
+                }
+            }
+
+            let scale = m.get(col, col).recip();
+            for c in 0..4 {
+                m.set(col, c, m.get(col, c) * scale);
+                inv.set(col, c, inv.get(col, c) * scale);
+            }
+
+            for row in 0..4 {
+                if row == col {
+                    continue;
+                }
+                let fact = m.get(row, col);
+                if fact == 0. {
+                    continue;
+                }
+                for c in 0..4 {
+                    m.set(row, c, m.get(row, c) - fact * m.get(col, c));
+                    inv.set(row, c, inv.get(row, c) - fact * inv.get(col, c));
+                }
+            }
+        }
+        Some(inv)
+    }
+
+    pub fn translate(v: &Vec3) -> Mat4 {
+        Mat4::from_rows([
+            [1., 0., 0., *v.x()],
+            [0., 1., 0., *v.y()],
+            [0., 0., 1., *v.z()],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    pub fn scale(x: f64, y: f64, z: f64) -> Mat4 {
+        Mat4::from_rows([
+            [x, 0., 0., 0.],
+            [0., y, 0., 0.],
+            [0., 0., z, 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    // Counter-clockwise rotation of `angle` radians about `axis` (Rodrigues' formula)
+    pub fn rotate(axis: &Vec3, angle: f64) -> Mat4 {
+        let a = axis.norm();
+        let (x, y, z) = (*a.x(), *a.y(), *a.z());
+        let (sin, cos) = angle.sin_cos();
+        let k = 1. - cos;
+        Mat4::from_rows([
+            [x * x * k + cos, x * y * k - z * sin, x * z * k + y * sin, 0.],
+            [y * x * k + z * sin, y * y * k + cos, y * z * k - x * sin, 0.],
+            [z * x * k - y * sin, z * y * k + x * sin, z * z * k + cos, 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    pub fn rotate_x(angle: f64) -> Mat4 {
+        Mat4::rotate(&Vec3::new(1., 0., 0.), angle)
+    }
+
+    pub fn rotate_y(angle: f64) -> Mat4 {
+        Mat4::rotate(&Vec3::new(0., 1., 0.), angle)
+    }
+
+    pub fn rotate_z(angle: f64) -> Mat4 {
+        Mat4::rotate(&Vec3::new(0., 0., 1.), angle)
+    }
+
+    // World to camera (view) transform, the camera looks down its local -z axis
+    pub fn look_at(eye: &Point3, target: &Point3, up: &Vec3) -> Mat4 {
+        let forward = target.sub(eye).norm();
+        let right = forward.cross(up).norm();
+        let true_up = right.cross(&forward);
+        let e = eye.sub(&Point3::default());
+        Mat4::from_rows([
+            [*right.x(), *right.y(), *right.z(), -right.dot(&e)],
+            [*true_up.x(), *true_up.y(), *true_up.z(), -true_up.dot(&e)],
+            [-*forward.x(), -*forward.y(), -*forward.z(), forward.dot(&e)],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    // OpenGL style projection, maps the view frustum to the [-1,1] cube (fov_y in radians)
+    pub fn perspective(fov_y: f64, aspect: f64, near: f64, far: f64) -> Mat4 {
+        let f = (fov_y / 2.).tan().recip();
+        let depth = near - far;
+        Mat4::from_rows([
+            [f / aspect, 0., 0., 0.],
+            [0., f, 0., 0.],
+            [0., 0., (far + near) / depth, 2. * far * near / depth],
+            [0., 0., -1., 0.],
+        ])
+    }
+
+    pub fn transform_point(&self, p: &Point3) -> Point3 {
+        let (x, y, z) = (*p.x(), *p.y(), *p.z());
+        let tx = self.get(0, 0) * x + self.get(0, 1) * y + self.get(0, 2) * z + self.get(0, 3);
+        let ty = self.get(1, 0) * x + self.get(1, 1) * y + self.get(1, 2) * z + self.get(1, 3);
+        let tz = self.get(2, 0) * x + self.get(2, 1) * y + self.get(2, 2) * z + self.get(2, 3);
+        let w = self.get(3, 0) * x + self.get(3, 1) * y + self.get(3, 2) * z + self.get(3, 3);
+        if w == 1. {
+            Point3::new(tx, ty, tz)
+        } else {
+            Point3::new(tx / w, ty / w, tz / w)
+        }
+    }
+
+    // Bound on the error of transform_point(p) for an affine matrix, where p already
+    // carries `p_error`
+    pub fn transform_point_error(&self, p: &Point3, p_error: &Vec3) -> Vec3 {
+        let (x, y, z) = (*p.x(), *p.y(), *p.z());
+        let (ex, ey, ez) = (*p_error.x(), *p_error.y(), *p_error.z());
+        let row = |r: usize| {
+            let m = |c: usize| self.get(r, c);
+            let rounding = (m(0) * x).abs() + (m(1) * y).abs() + (m(2) * z).abs() + m(3).abs();
+            let carried = m(0).abs() * ex + m(1).abs() * ey + m(2).abs() * ez;
+            gamma(3) * rounding + (1. + gamma(3)) * carried
+        };
+        Vec3::new(row(0), row(1), row(2))
+    }
+
+    // Ignores translation
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let (x, y, z) = (*v.x(), *v.y(), *v.z());
+        Vec3::new(
+            self.get(0, 0) * x + self.get(0, 1) * y + self.get(0, 2) * z,
+            self.get(1, 0) * x + self.get(1, 1) * y + self.get(1, 2) * z,
+            self.get(2, 0) * x + self.get(2, 1) * y + self.get(2, 2) * z,
+        )
+    }
+
+    // Normals transform by the inverse transpose of the upper 3x3, result is not normalized
+    pub fn transform_normal(&self, n: &Vec3) -> Vec3 {
+        let m = |r: usize, c: usize| self.get(r, c);
+        // cofactor matrix of the upper 3x3 == det * inverse transpose
+        let c00 = m(1, 1) * m(2, 2) - m(1, 2) * m(2, 1);
+        let c01 = m(1, 2) * m(2, 0) - m(1, 0) * m(2, 2);
+        let c02 = m(1, 0) * m(2, 1) - m(1, 1) * m(2, 0);
+        let c10 = m(0, 2) * m(2, 1) - m(0, 1) * m(2, 2);
+        let c11 = m(0, 0) * m(2, 2) - m(0, 2) * m(2, 0);
+        let c12 = m(0, 1) * m(2, 0) - m(0, 0) * m(2, 1);
+        let c20 = m(0, 1) * m(1, 2) - m(0, 2) * m(1, 1);
+        let c21 = m(0, 2) * m(1, 0) - m(0, 0) * m(1, 2);
+        let c22 = m(0, 0) * m(1, 1) - m(0, 1) * m(1, 0);
+        let det = m(0, 0) * c00 + m(0, 1) * c01 + m(0, 2) * c02;
+        let inv_det = det.recip();
+
+        let (x, y, z) = (*n.x(), *n.y(), *n.z());
+        Vec3::new(
+            (c00 * x + c01 * y + c02 * z) * inv_det,
+            (c10 * x + c11 * y + c12 * z) * inv_det,
+            (c20 * x + c21 * y + c22 * z) * inv_det,
+        )
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Mat4::identity()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[allow(unused_imports)]
+    use crate::approx::RelativeEq;
+
+    use std::f64::consts::PI;
+
+    fn assert_mat_eq(a: &Mat4, b: &Mat4) {
+        for i in 0..16 {
+            assert_relative_eq!(a.data[i], b.data[i], epsilon = 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_inverse() {
+        let m = Mat4::translate(&Vec3::new(1., -2., 3.))
+            .mul(&Mat4::rotate(&Vec3::new(1., 1., 0.), 0.7))
+            .mul(&Mat4::scale(2., 3., 0.5));
+        let inv = m.inverse().unwrap();
+        assert_mat_eq(&m.mul(&inv), &Mat4::identity());
+        assert_relative_eq!(m.determinant(), 3., epsilon = 1e-9);
+        assert!(Mat4::scale(1., 0., 1.).inverse().is_none());
+    }
+
+    #[test]
+    fn test_rotate_point() {
+        let p = Mat4::rotate_z(PI / 2.).transform_point(&Point3::new(1., 0., 0.));
+        assert_relative_eq!(*p.x(), 0., epsilon = 1e-9);
+        assert_relative_eq!(*p.y(), 1., epsilon = 1e-9);
+        let p = Mat4::translate(&Vec3::new(1., 2., 3.)).transform_point(&p);
+        assert_relative_eq!(*p.z(), 3., epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_transform_normal() {
+        // a 45 degree plane squashed along y, its normal must stay perpendicular to the surface
+        let m = Mat4::scale(1., 0.5, 1.);
+        let tangent = m.transform_vector(&Vec3::new(1., -1., 0.));
+        let normal = m.transform_normal(&Vec3::new(1., 1., 0.));
+        assert_relative_eq!(tangent.dot(&normal), 0., epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_look_at() {
+        let view = Mat4::look_at(&Point3::new(0., 0., 5.), &Point3::default(), &Vec3::new(0., 1., 0.));
+        let p = view.transform_point(&Point3::default());
+        assert_relative_eq!(*p.z(), -5., epsilon = 1e-9);
+    }
 }
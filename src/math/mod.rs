@@ -1,4 +1,11 @@
+pub mod aabb;
 pub mod mat4;
 pub mod point3;
 pub mod ray;
 pub mod vec3;
+
+// Bound on the relative error after n rounded floating point operations
+pub fn gamma(n: u32) -> f64 {
+    let e = f64::EPSILON * 0.5;
+    f64::from(n) * e / (1. - f64::from(n) * e)
+}
@@ -24,6 +24,15 @@ impl Point3 {
         &self.z
     }
 
+    pub fn axis(&self, i: usize) -> f64 {
+        match i {
+            0 => self.x,
+            1 => self.y,
+            2 => self.z,
+            _ => panic!("Invalid axis {}", i),
+        }
+    }
+
     pub fn add(&self, v: &Vec3) -> Point3 {
         Point3 {
             x: self.x + v.x(),
@@ -36,12 +45,13 @@ impl Point3 {
         Vec3::new(self.x - p.x(), self.y - p.y(), self.z - p.z())
     }
 
-    pub fn clone(&self) -> Point3 {
-        Point3 {
-            x: self.x,
-            y: self.y,
-            z: self.z,
-        }
+    pub fn as_vec3(&self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+
+    // Magnitude of each coordinate, for error bounds
+    pub fn abs(&self) -> Vec3 {
+        Vec3::new(self.x.abs(), self.y.abs(), self.z.abs())
     }
 }
 
@@ -1,30 +1,43 @@
 use crate::math::point3::Point3;
 use crate::math::vec3::Vec3;
+use crate::primitives::material::MediumStack;
 
-#[derive(Debug)]
+// Fraction of the distance to a light that shadow rays leave out, so the emitting
+// surface itself never counts as an occluder
+const SHADOW_EPSILON: f64 = 1e-6;
+
+// Half line from origin along direction, only hits at t_min < t < t_max count
+#[derive(Debug, Clone)]
 pub struct Ray {
     pub origin: Point3,
     pub direction: Vec3,
-    pub t: f64,
+    pub t_min: f64,
+    pub t_max: f64,
+    pub media: MediumStack, // volumes the ray is inside of, innermost last
 }
 
 impl Ray {
-    pub fn new_norm(o: Point3, d: Vec3, len: f64) -> Ray {
-        Ray {
-            origin: o,
-            direction: d.norm(),
-            t: len,
-        }
+    pub fn new_norm(o: Point3, d: Vec3) -> Ray {
+        Ray::new(o, d.norm())
     }
 
-    pub fn new(o: Point3, d: Vec3, len: f64) -> Ray {
+    pub fn new(o: Point3, d: Vec3) -> Ray {
         Ray {
             origin: o,
             direction: d, // no normalization
-            t: len,
+            t_min: 0.,
+            t_max: f64::INFINITY,
+            media: MediumStack::new(),
         }
     }
 
+    // Visibility test toward a point `dist` away, stops just short of the point itself
+    pub fn shadow(o: Point3, d: Vec3, dist: f64) -> Ray {
+        let mut ray = Ray::new(o, d);
+        ray.t_max = dist * (1. - SHADOW_EPSILON);
+        ray
+    }
+
     pub fn norm(&mut self) -> &mut Self {
         self.direction = self.direction.norm();
         self
@@ -38,39 +51,15 @@ impl Ray {
         &self.direction
     }
 
-    pub fn t(&self) -> &f64 {
-        &self.t
-    }
-
-    pub fn set_t(&mut self, val: f64) {
-        self.t = val;
-    }
-
-    pub fn as_point3(&self) -> Point3 {
-        self.origin.add(&self.direction.scale(self.t))
+    pub fn in_range(&self, t: f64) -> bool {
+        t > self.t_min && t < self.t_max
     }
 
     pub fn at_t(&self, t: f64) -> Point3 {
         self.origin.add(&self.direction.scale(t))
     }
 
-    pub fn clone(&self) -> Self {
-        Ray {
-            origin: self.origin,
-            direction: self.direction,
-            t: self.t,
-        }
-    }
-
     pub fn dot(&self, other: &Ray) -> f64 {
         self.direction.dot(&other.direction)
     }
-
-    pub fn clone_with_t(&self, t: f64) -> Self {
-        Ray {
-            origin: self.origin,
-            direction: self.direction,
-            t: t,
-        }
-    }
 }
@@ -24,6 +24,15 @@ impl Vec3 {
         &self.z
     }
 
+    pub fn axis(&self, i: usize) -> f64 {
+        match i {
+            0 => self.x,
+            1 => self.y,
+            2 => self.z,
+            _ => panic!("Invalid axis {}", i),
+        }
+    }
+
     pub fn add(&self, p: &Vec3) -> Vec3 {
         Vec3 {
             x: self.x + p.x(),
@@ -75,7 +84,7 @@ impl Vec3 {
     pub fn cross(&self, v: &Vec3) -> Vec3 {
         Vec3 {
             x: (self.y * v.z()) - (self.z * v.y()),
-            y: (self.x * v.z()) - (self.z * v.x()),
+            y: (self.z * v.x()) - (self.x * v.z()),
             z: (self.x * v.y()) - (self.y * v.x()),
         }
     }
@@ -89,7 +98,49 @@ impl Vec3 {
         }
     }
 
+    // Two unit vectors perpendicular to this (unit) vector, Duff et al. 2017
+    pub fn orthonormal_basis(&self) -> (Vec3, Vec3) {
+        let sign = 1_f64.copysign(self.z);
+        let a = -1. / (sign + self.z);
+        let b = self.x * self.y * a;
+        (
+            Vec3::new(1. + sign * self.x * self.x * a, sign * b, -sign * self.x),
+            Vec3::new(b, sign + self.y * self.y * a, -self.y),
+        )
+    }
+
+    pub fn abs(&self) -> Vec3 {
+        Vec3::new(self.x.abs(), self.y.abs(), self.z.abs())
+    }
+
     pub fn as_point3(&self) -> Point3 {
         Point3::new(self.x, self.y, self.z)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[allow(unused_imports)]
+    use crate::approx::RelativeEq;
+
+    #[test]
+    fn test_cross() {
+        let x = Vec3::new(1., 0., 0.);
+        let y = Vec3::new(0., 1., 0.);
+        let z = Vec3::new(0., 0., 1.);
+        // every pair of axes gives the third by the right hand rule
+        for (a, b, c) in [(x, y, z), (y, z, x), (z, x, y)].iter() {
+            let n = a.cross(b);
+            assert_relative_eq!(*n.x(), *c.x());
+            assert_relative_eq!(*n.y(), *c.y());
+            assert_relative_eq!(*n.z(), *c.z());
+        }
+
+        let u = Vec3::new(1., 2., 3.);
+        let v = Vec3::new(-2., 0.5, 4.);
+        let n = u.cross(&v);
+        assert_relative_eq!(n.dot(&u), 0.);
+        assert_relative_eq!(n.dot(&v), 0.);
+    }
+}
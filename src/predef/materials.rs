@@ -6,6 +6,7 @@ pub const GLASS: Material = Material::Refractive {
     spec_col: colors::WHITE,
     refr_col: colors::WHITE,
     ior: 1.5,
+    priority: 0,
 };
 
 #[allow(dead_code)]
@@ -13,4 +14,5 @@ pub const WATER: Material = Material::Refractive {
     spec_col: colors::WHITE,
     refr_col: colors::WHITE,
     ior: (4./3.),
+    priority: 0,
 };
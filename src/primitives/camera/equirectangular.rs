@@ -0,0 +1,91 @@
+use std::f64::consts::PI;
+
+use crate::math::point3::Point3;
+use crate::math::ray::Ray;
+use crate::math::vec3::Vec3;
+
+use super::{Camera, CameraFrame};
+
+// Full 360 by 180 degree panorama, longitude across and latitude down the image
+#[derive(Debug)]
+pub struct EquirectangularCamera {
+    pub frame: CameraFrame,
+    pub res_x: u32,
+    pub res_y: u32,
+}
+
+impl EquirectangularCamera {
+    pub fn new(frame: CameraFrame, r_x: u32, r_y: u32) -> EquirectangularCamera {
+        EquirectangularCamera {
+            frame,
+            res_x: r_x,
+            res_y: r_y,
+        }
+    }
+
+    pub fn look_at(eye: &Point3, target: &Point3, up: &Vec3) -> EquirectangularCamera {
+        EquirectangularCamera {
+            frame: CameraFrame::look_at(eye, target, up),
+            ..Default::default()
+        }
+    }
+
+    pub fn set_resolution(&mut self, x: u32, y: u32) -> &mut Self {
+        self.res_x = x;
+        self.res_y = y;
+        self
+    }
+}
+
+impl Camera for EquirectangularCamera {
+    fn generate_ray(&self, raster_x: f64, raster_y: f64, _lens: (f64, f64)) -> Option<Ray> {
+        let u = raster_x / f64::from(self.res_x);
+        let v = raster_y / f64::from(self.res_y);
+        // the view vector sits in the middle of the image
+        let longitude = (u - 0.5) * 2. * PI;
+        let latitude = (0.5 - v) * PI;
+        let (sin_lat, cos_lat) = latitude.sin_cos();
+        let dir = self.frame.to_world(
+            cos_lat * longitude.sin(),
+            sin_lat,
+            cos_lat * longitude.cos(),
+        );
+        Some(Ray::new(self.frame.origin, dir.norm()))
+    }
+
+    fn resolution(&self) -> (u32, u32) {
+        (self.res_x, self.res_y)
+    }
+
+    fn set_resolution(&mut self, x: u32, y: u32) {
+        EquirectangularCamera::set_resolution(self, x, y);
+    }
+}
+
+impl Default for EquirectangularCamera {
+    fn default() -> EquirectangularCamera {
+        EquirectangularCamera::new(CameraFrame::default(), 1024, 512)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[allow(unused_imports)]
+    use crate::approx::RelativeEq;
+
+    #[test]
+    fn test_equirectangular_directions() {
+        let camera = EquirectangularCamera::default();
+        let dir = |x, y| {
+            let d = *camera.generate_ray(x, y, (0.5, 0.5)).unwrap().direction();
+            (*d.x(), *d.y(), *d.z())
+        };
+        assert_relative_eq!(dir(512., 256.).2, -1., epsilon = 1e-9);
+        // a quarter turn to the right
+        assert_relative_eq!(dir(768., 256.).0, 1., epsilon = 1e-9);
+        // straight up at the top row, behind the camera at the edges
+        assert_relative_eq!(dir(512., 0.).1, 1., epsilon = 1e-9);
+        assert_relative_eq!(dir(0., 256.).2, 1., epsilon = 1e-9);
+    }
+}
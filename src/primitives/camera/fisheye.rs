@@ -0,0 +1,130 @@
+use crate::math::point3::Point3;
+use crate::math::ray::Ray;
+use crate::math::vec3::Vec3;
+
+use super::{raster_to_ndc, Camera, CameraFrame};
+
+// How the distance from the image center maps to the angle off the view vector
+#[derive(Debug, Copy, Clone)]
+pub enum FisheyeMapping {
+    // angle grows linearly with the radius
+    Equidistant,
+    // preserves solid angle, r = 2 sin(theta / 2)
+    Equisolid,
+}
+
+// Circular fisheye, the image circle is inscribed in the shorter side of the image
+#[derive(Debug)]
+pub struct FisheyeCamera {
+    pub frame: CameraFrame,
+    mapping: FisheyeMapping,
+    fov: f64, // degrees across the image circle, up to 360
+    pub res_x: u32,
+    pub res_y: u32,
+}
+
+impl FisheyeCamera {
+    pub fn new(
+        frame: CameraFrame,
+        mapping: FisheyeMapping,
+        fov: f64,
+        r_x: u32,
+        r_y: u32,
+    ) -> FisheyeCamera {
+        FisheyeCamera {
+            frame,
+            mapping,
+            fov,
+            res_x: r_x,
+            res_y: r_y,
+        }
+    }
+
+    pub fn look_at(eye: &Point3, target: &Point3, up: &Vec3) -> FisheyeCamera {
+        FisheyeCamera {
+            frame: CameraFrame::look_at(eye, target, up),
+            ..Default::default()
+        }
+    }
+
+    pub fn fov(&self) -> f64 {
+        self.fov
+    }
+
+    pub fn set_fov(&mut self, degrees: f64) -> &mut Self {
+        self.fov = degrees;
+        self
+    }
+
+    pub fn set_mapping(&mut self, mapping: FisheyeMapping) -> &mut Self {
+        self.mapping = mapping;
+        self
+    }
+
+    pub fn set_resolution(&mut self, x: u32, y: u32) -> &mut Self {
+        self.res_x = x;
+        self.res_y = y;
+        self
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn generate_ray(&self, raster_x: f64, raster_y: f64, _lens: (f64, f64)) -> Option<Ray> {
+        let (ndc_x, ndc_y) = raster_to_ndc(raster_x, raster_y, self.res_x, self.res_y);
+        // scale so the shorter side spans [-1, 1]
+        let (res_x, res_y) = (f64::from(self.res_x), f64::from(self.res_y));
+        let min = res_x.min(res_y);
+        let (x, y) = (ndc_x * res_x / min, ndc_y * res_y / min);
+
+        let r = (x * x + y * y).sqrt();
+        if r > 1. {
+            return None; // outside the image circle
+        }
+        let half_fov = self.fov.to_radians() / 2.;
+        let theta = match self.mapping {
+            FisheyeMapping::Equidistant => r * half_fov,
+            FisheyeMapping::Equisolid => 2. * (r * (half_fov / 2.).sin()).asin(),
+        };
+        let phi = y.atan2(x);
+        let (sin_theta, cos_theta) = theta.sin_cos();
+        let dir = self
+            .frame
+            .to_world(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        Some(Ray::new(self.frame.origin, dir.norm()))
+    }
+
+    fn resolution(&self) -> (u32, u32) {
+        (self.res_x, self.res_y)
+    }
+
+    fn set_resolution(&mut self, x: u32, y: u32) {
+        FisheyeCamera::set_resolution(self, x, y);
+    }
+}
+
+impl Default for FisheyeCamera {
+    fn default() -> FisheyeCamera {
+        FisheyeCamera::new(CameraFrame::default(), FisheyeMapping::Equidistant, 180., 640, 480)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[allow(unused_imports)]
+    use crate::approx::RelativeEq;
+
+    #[test]
+    fn test_fisheye_edge_angle() {
+        for mapping in [FisheyeMapping::Equidistant, FisheyeMapping::Equisolid].iter() {
+            let camera = FisheyeCamera::new(CameraFrame::default(), *mapping, 180., 200, 100);
+            // the image circle touches the top of the image, 90 degrees off the view vector
+            let top = camera.generate_ray(100., 0., (0.5, 0.5)).unwrap();
+            assert_relative_eq!(*top.direction().z(), 0., epsilon = 1e-9);
+            assert_relative_eq!(*top.direction().y(), 1., epsilon = 1e-9);
+            let center = camera.generate_ray(100., 50., (0.5, 0.5)).unwrap();
+            assert_relative_eq!(*center.direction().z(), -1., epsilon = 1e-9);
+            assert!(camera.generate_ray(0., 0., (0.5, 0.5)).is_none());
+        }
+    }
+}
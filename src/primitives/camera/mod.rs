@@ -0,0 +1,135 @@
+use crate::math::mat4::Mat4;
+use crate::math::point3::Point3;
+use crate::math::ray::Ray;
+use crate::math::vec3::Vec3;
+
+pub mod equirectangular;
+pub mod fisheye;
+pub mod orthographic;
+pub mod perspective;
+
+pub use equirectangular::EquirectangularCamera;
+pub use fisheye::{FisheyeCamera, FisheyeMapping};
+pub use orthographic::OrthographicCamera;
+pub use perspective::PerspectiveCamera;
+
+// Maps raster positions to rays, (0,0) is the top left corner of the image
+pub trait Camera: std::fmt::Debug + Send + Sync {
+    // `lens` is a uniform sample in [0,1)^2 for cameras with an aperture,
+    // None where the raster position doesn't map to a direction
+    fn generate_ray(&self, raster_x: f64, raster_y: f64, lens: (f64, f64)) -> Option<Ray>;
+    fn resolution(&self) -> (u32, u32);
+    fn set_resolution(&mut self, x: u32, y: u32);
+}
+
+// Raster position to [-1,1] on both axes with y pointing up
+pub fn raster_to_ndc(raster_x: f64, raster_y: f64, res_x: u32, res_y: u32) -> (f64, f64) {
+    let res_x = f64::from(res_x);
+    let res_y = f64::from(res_y);
+    let flipped_y = res_y - raster_y; // invert so y points upward
+    (2. * (raster_x / res_x) - 1., 2. * (flipped_y / res_y) - 1.)
+}
+
+// Position and orthonormal orientation shared by every camera
+#[derive(Debug, Clone)]
+pub struct CameraFrame {
+    pub origin: Point3,
+    view_vec: Vec3,
+    up: Vec3,       // kept orthonormal to view_vec
+    right: Vec3,
+    world_up: Vec3, // reference direction the frame is built from
+    roll: f64,      // degrees around the view vector
+}
+
+impl CameraFrame {
+    pub fn new(o: Point3, v: Vec3, u: Vec3) -> CameraFrame {
+        let mut frame = CameraFrame {
+            origin: o,
+            view_vec: v.norm(),
+            up: u,
+            right: Vec3::new(1., 0., 0.),
+            world_up: u,
+            roll: 0.,
+        };
+        frame.update();
+        frame
+    }
+
+    pub fn look_at(eye: &Point3, target: &Point3, up: &Vec3) -> CameraFrame {
+        CameraFrame::new(*eye, target.sub(eye), *up)
+    }
+
+    // Rebuilds right/up around the view vector, rolled by `roll`
+    fn update(&mut self) {
+        let mut right = self.view_vec.cross(&self.world_up);
+        if right.square() < 1e-12 {
+            // looking straight along world_up, any perpendicular will do
+            right = self.view_vec.orthonormal_basis().0;
+        }
+        let right = right.norm();
+        let up = right.cross(&self.view_vec).norm();
+
+        let roll = Mat4::rotate(&self.view_vec, self.roll.to_radians());
+        self.right = roll.transform_vector(&right);
+        self.up = roll.transform_vector(&up);
+    }
+
+    pub fn view_vec(&self) -> &Vec3 {
+        &self.view_vec
+    }
+
+    pub fn up(&self) -> &Vec3 {
+        &self.up
+    }
+
+    pub fn right(&self) -> &Vec3 {
+        &self.right
+    }
+
+    pub fn set_up(&mut self, up: &Vec3) -> &mut Self {
+        self.world_up = *up;
+        self.update();
+        self
+    }
+
+    // Clockwise roll in degrees as seen through the camera
+    pub fn set_roll(&mut self, degrees: f64) -> &mut Self {
+        self.roll = degrees;
+        self.update();
+        self
+    }
+
+    // Moves the camera while it keeps looking at the point `target_dist` ahead of it
+    pub fn set_origin(&mut self, p: Point3, target_dist: f64) -> &mut Self {
+        let target = self.origin.add(&self.view_vec.scale(target_dist));
+        self.origin = p;
+        if target.sub(&p).square() > 0. {
+            self.view_vec = target.sub(&p).norm();
+            self.update();
+        }
+        self
+    }
+
+    pub fn point_at(&mut self, p: &Point3) -> &mut Self {
+        self.view_vec = p.sub(&self.origin).norm();
+        self.update();
+        self
+    }
+
+    // Camera space (x right, y up, z forward) to world space
+    pub fn to_world(&self, x: f64, y: f64, z: f64) -> Vec3 {
+        self.right
+            .scale(x)
+            .add(&self.up.scale(y))
+            .add(&self.view_vec.scale(z))
+    }
+}
+
+impl Default for CameraFrame {
+    fn default() -> Self {
+        let o = Point3::new(0., 0., 0.);
+        let v = Vec3::new(0., 0., -1.); // negative z
+        let u = Vec3::new(0., 1., 0.);
+        CameraFrame::new(o, v, u)
+    }
+}
@@ -0,0 +1,92 @@
+use crate::math::point3::Point3;
+use crate::math::ray::Ray;
+use crate::math::vec3::Vec3;
+
+use super::{raster_to_ndc, Camera, CameraFrame};
+
+// Parallel projection, every ray travels along the view vector
+#[derive(Debug)]
+pub struct OrthographicCamera {
+    pub frame: CameraFrame,
+    width: f64, // extent of the image plane in scene units
+    pub res_x: u32,
+    pub res_y: u32,
+}
+
+impl OrthographicCamera {
+    pub fn new(frame: CameraFrame, width: f64, r_x: u32, r_y: u32) -> OrthographicCamera {
+        OrthographicCamera {
+            frame,
+            width,
+            res_x: r_x,
+            res_y: r_y,
+        }
+    }
+
+    pub fn look_at(eye: &Point3, target: &Point3, up: &Vec3) -> OrthographicCamera {
+        OrthographicCamera {
+            frame: CameraFrame::look_at(eye, target, up),
+            ..Default::default()
+        }
+    }
+
+    pub fn width(&self) -> f64 {
+        self.width
+    }
+
+    pub fn set_width(&mut self, width: f64) -> &mut Self {
+        self.width = width;
+        self
+    }
+
+    pub fn set_resolution(&mut self, x: u32, y: u32) -> &mut Self {
+        self.res_x = x;
+        self.res_y = y;
+        self
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn generate_ray(&self, raster_x: f64, raster_y: f64, _lens: (f64, f64)) -> Option<Ray> {
+        let (ndc_x, ndc_y) = raster_to_ndc(raster_x, raster_y, self.res_x, self.res_y);
+        let half_width = self.width / 2.;
+        let half_height = half_width * f64::from(self.res_y) / f64::from(self.res_x);
+        let offset = self.frame.to_world(ndc_x * half_width, ndc_y * half_height, 0.);
+        Some(Ray::new(self.frame.origin.add(&offset), *self.frame.view_vec()))
+    }
+
+    fn resolution(&self) -> (u32, u32) {
+        (self.res_x, self.res_y)
+    }
+
+    fn set_resolution(&mut self, x: u32, y: u32) {
+        OrthographicCamera::set_resolution(self, x, y);
+    }
+}
+
+impl Default for OrthographicCamera {
+    fn default() -> OrthographicCamera {
+        OrthographicCamera::new(CameraFrame::default(), 4., 640, 480)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[allow(unused_imports)]
+    use crate::approx::RelativeEq;
+
+    #[test]
+    fn test_orthographic_rays() {
+        let camera = OrthographicCamera::new(CameraFrame::default(), 4., 200, 100);
+        // the corner of the image is half the width and height away from the center
+        let corner = camera.generate_ray(0., 0., (0.5, 0.5)).unwrap();
+        assert_relative_eq!(*corner.origin().x(), -2., epsilon = 1e-9);
+        assert_relative_eq!(*corner.origin().y(), 1., epsilon = 1e-9);
+        assert_relative_eq!(*corner.origin().z(), 0., epsilon = 1e-9);
+        assert_relative_eq!(*corner.direction().z(), -1., epsilon = 1e-9);
+        let center = camera.generate_ray(100., 50., (0.5, 0.5)).unwrap();
+        assert_relative_eq!(center.origin().sub(&Point3::default()).mag(), 0., epsilon = 1e-9);
+        assert_relative_eq!(center.dot(&corner), 1., epsilon = 1e-9);
+    }
+}
@@ -0,0 +1,257 @@
+use crate::math::point3::Point3;
+use crate::math::ray::Ray;
+use crate::math::vec3::Vec3;
+use crate::sampler;
+
+use super::{raster_to_ndc, Camera, CameraFrame};
+
+// Thin lens camera, lengths are in scene units (meters for the defaults)
+#[derive(Debug)]
+pub struct PerspectiveCamera {
+    pub frame: CameraFrame,
+    fstop: f64,          // f-number, infinite for a pinhole
+    film_size: f64,      // width of the sensor
+    focal_length: f64,
+    focus_distance: f64, // distance along the view vector that is in focus
+    aspect_ratio: Option<f64>, // width / height, None follows the resolution
+    pub res_x: u32,
+    pub res_y: u32,
+}
+
+impl PerspectiveCamera {
+    pub fn new(
+        o: Point3,
+        v: Vec3,
+        u: Vec3,
+        f: f64,
+        size: f64,
+        r_x: u32,
+        r_y: u32,
+    ) -> PerspectiveCamera {
+        PerspectiveCamera {
+            frame: CameraFrame::new(o, v, u),
+            fstop: f,
+            film_size: size,
+            focal_length: size * 0.375,
+            focus_distance: 1.,
+            aspect_ratio: None,
+            res_x: r_x,
+            res_y: r_y,
+        }
+    }
+
+    pub fn look_at(eye: &Point3, target: &Point3, up: &Vec3) -> PerspectiveCamera {
+        PerspectiveCamera {
+            frame: CameraFrame::look_at(eye, target, up),
+            ..Default::default()
+        }
+    }
+
+    pub fn view_vec(&self) -> &Vec3 {
+        self.frame.view_vec()
+    }
+
+    pub fn up(&self) -> &Vec3 {
+        self.frame.up()
+    }
+
+    pub fn right(&self) -> &Vec3 {
+        self.frame.right()
+    }
+
+    pub fn set_up(&mut self, up: &Vec3) -> &mut Self {
+        self.frame.set_up(up);
+        self
+    }
+
+    // Clockwise roll in degrees as seen through the camera
+    pub fn set_roll(&mut self, degrees: f64) -> &mut Self {
+        self.frame.set_roll(degrees);
+        self
+    }
+
+    pub fn set_fstop(&mut self, f: f64) -> &mut Self {
+        self.fstop = f;
+        self
+    }
+
+    pub fn set_film_size(&mut self, size: f64) -> &mut Self {
+        self.film_size = size;
+        self
+    }
+
+    pub fn set_focal_length(&mut self, length: f64) -> &mut Self {
+        self.focal_length = length;
+        self
+    }
+
+    pub fn set_focus_distance(&mut self, dist: f64) -> &mut Self {
+        self.focus_distance = dist;
+        self
+    }
+
+    // Moves the plane of focus through p
+    pub fn focus_on(&mut self, p: &Point3) -> &mut Self {
+        self.focus_distance = p.sub(&self.frame.origin).dot(self.frame.view_vec());
+        self
+    }
+
+    // The lens diameter is focal_length / fstop, a pinhole when fstop is infinite
+    pub fn aperture_radius(&self) -> f64 {
+        if self.fstop.is_finite() && self.fstop > 0. {
+            self.focal_length / (2. * self.fstop)
+        } else {
+            0.
+        }
+    }
+
+    pub fn aspect_ratio(&self) -> f64 {
+        self.aspect_ratio
+            .unwrap_or_else(|| f64::from(self.res_x) / f64::from(self.res_y))
+    }
+
+    // Overrides the width / height ratio derived from the resolution
+    pub fn set_aspect_ratio(&mut self, ratio: Option<f64>) -> &mut Self {
+        self.aspect_ratio = ratio;
+        self
+    }
+
+    // Horizontal field of view in degrees, set through the focal length
+    pub fn fov(&self) -> f64 {
+        2. * (self.film_size / (2. * self.focal_length)).atan().to_degrees()
+    }
+
+    pub fn set_fov(&mut self, degrees: f64) -> &mut Self {
+        self.focal_length = self.film_size / (2. * (degrees.to_radians() / 2.).tan());
+        self
+    }
+
+    pub fn vertical_fov(&self) -> f64 {
+        let half_width = self.film_size / (2. * self.focal_length);
+        2. * (half_width / self.aspect_ratio()).atan().to_degrees()
+    }
+
+    pub fn set_vertical_fov(&mut self, degrees: f64) -> &mut Self {
+        let half_height = (degrees.to_radians() / 2.).tan();
+        self.focal_length = self.film_size / (2. * half_height * self.aspect_ratio());
+        self
+    }
+
+    pub fn set_resolution(&mut self, x: u32, y: u32) -> &mut Self {
+        self.res_x = x;
+        self.res_y = y;
+        self
+    }
+
+    // Moves the camera while it keeps looking at the point it was focused on
+    pub fn set_origin(&mut self, p: Point3) -> &mut Self {
+        self.frame.set_origin(p, self.focus_distance);
+        self
+    }
+
+    pub fn point_at(&mut self, p: &Point3) -> &mut Self {
+        self.frame.point_at(p);
+        self
+    }
+}
+
+impl Camera for PerspectiveCamera {
+    fn generate_ray(&self, raster_x: f64, raster_y: f64, lens: (f64, f64)) -> Option<Ray> {
+        let (ndc_x, ndc_y) = raster_to_ndc(raster_x, raster_y, self.res_x, self.res_y);
+
+        // image plane at unit distance, its half width is tan(fov / 2)
+        let half_width = self.film_size / (2. * self.focal_length);
+        let half_height = half_width / self.aspect_ratio();
+        let dir = self.frame.to_world(ndc_x * half_width, ndc_y * half_height, 1.);
+        let origin = self.frame.origin;
+
+        let radius = self.aperture_radius();
+        if radius == 0. {
+            return Some(Ray::new(origin, dir.norm()));
+        }
+
+        // every ray through the lens converges on the plane of focus
+        let focus_point = origin.add(&dir.scale(self.focus_distance));
+        let (lens_x, lens_y) = sampler::concentric_disk(lens);
+        let lens_point = origin.add(&self.frame.to_world(lens_x * radius, lens_y * radius, 0.));
+        Some(Ray::new(lens_point, focus_point.sub(&lens_point).norm()))
+    }
+
+    fn resolution(&self) -> (u32, u32) {
+        (self.res_x, self.res_y)
+    }
+
+    fn set_resolution(&mut self, x: u32, y: u32) {
+        PerspectiveCamera::set_resolution(self, x, y);
+    }
+}
+
+impl Default for PerspectiveCamera {
+    fn default() -> PerspectiveCamera {
+        let frame = CameraFrame::default();
+        let mut camera = PerspectiveCamera::new(
+            frame.origin,
+            *frame.view_vec(),
+            *frame.up(),
+            f64::INFINITY,
+            0.036,
+            640,
+            480,
+        );
+        camera.set_focal_length(0.0135);
+        camera
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[allow(unused_imports)]
+    use crate::approx::RelativeEq;
+
+    fn center_ray(camera: &PerspectiveCamera, x: f64, y: f64) -> Ray {
+        camera.generate_ray(x, y, (0.5, 0.5)).unwrap()
+    }
+
+    #[test]
+    fn test_rays_converge_at_focus() {
+        let mut camera = PerspectiveCamera::default();
+        camera.set_fstop(1.4).focus_on(&Point3::new(0., 0., -2.));
+        assert!(camera.aperture_radius() > 0.);
+
+        let target = |lens| {
+            let ray = camera.generate_ray(100., 200., lens).unwrap();
+            let t = -2. / ray.direction().z();
+            ray.at_t(t)
+        };
+        let a = target((0.1, 0.9));
+        let b = target((0.8, 0.3));
+        assert_relative_eq!(*a.x(), *b.x(), epsilon = 1e-9);
+        assert_relative_eq!(*a.y(), *b.y(), epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_look_at_frame() {
+        let mut camera = PerspectiveCamera::look_at(
+            &Point3::new(5., 0., 0.),
+            &Point3::new(0., 0., 0.),
+            &Vec3::new(0., 1., 0.),
+        );
+        camera.set_resolution(200, 100).set_fov(90.);
+        assert_relative_eq!(camera.vertical_fov(), 2. * 0.5_f64.atan().to_degrees(), epsilon = 1e-9);
+
+        // the center ray looks at the target and the top of the image is +y
+        let center = center_ray(&camera, 100., 50.);
+        assert_relative_eq!(*center.direction().x(), -1., epsilon = 1e-9);
+        let top = center_ray(&camera, 100., 0.);
+        assert!(*top.direction().y() > 0.);
+        // right edge at 45 degrees
+        let right = center_ray(&camera, 200., 50.);
+        assert_relative_eq!(right.direction().dot(&center.direction), 0.5_f64.sqrt(), epsilon = 1e-9);
+        assert!(*right.direction().z() < 0.);
+
+        camera.set_roll(90.);
+        let right = center_ray(&camera, 200., 50.);
+        assert!(*right.direction().y() < 0.);
+    }
+}
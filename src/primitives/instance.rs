@@ -0,0 +1,100 @@
+use std::sync::Arc;
+
+use crate::math::aabb::Aabb;
+use crate::math::mat4::Mat4;
+use crate::math::point3::Point3;
+use crate::math::ray::Ray;
+use crate::math::vec3::Vec3;
+
+use super::Intersection;
+use super::Shadable;
+
+// A shared object placed in the world with its own object-to-world transform
+#[derive(Debug)]
+pub struct Instance {
+    object: Arc<dyn Shadable>,
+    to_world: Mat4,
+    to_object: Mat4,
+}
+
+impl Instance {
+    // None when the transform cannot be inverted, e.g. a scale of zero
+    pub fn new(object: Arc<dyn Shadable>, to_world: Mat4) -> Option<Instance> {
+        let to_object = to_world.inverse()?;
+        Some(Instance {
+            object,
+            to_world,
+            to_object,
+        })
+    }
+
+    pub fn transform(&self) -> &Mat4 {
+        &self.to_world
+    }
+
+    // Direction is left unnormalized so t and the interval mean the same thing in both spaces
+    fn to_object_ray(&self, ray: &Ray) -> Ray {
+        let mut local = Ray::new(
+            self.to_object.transform_point(ray.origin()),
+            self.to_object.transform_vector(ray.direction()),
+        );
+        local.t_min = ray.t_min;
+        local.t_max = ray.t_max;
+        local
+    }
+}
+
+impl Shadable for Instance {
+    fn normal(&self, p: &Point3) -> Option<Vec3> {
+        let local = self.to_object.transform_point(p);
+        self.object
+            .normal(&local)
+            .map(|n| self.to_world.transform_normal(&n).norm())
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Intersection<'_>> {
+        let local_ray = self.to_object_ray(ray);
+        self.object.intersect(&local_ray).map(|mut hit| {
+            hit.shading_normal = self.to_world.transform_normal(&hit.shading_normal).norm();
+            hit.geometric_normal = self.to_world.transform_normal(&hit.geometric_normal).norm();
+            hit.p_error = self.to_world.transform_point_error(&hit.p, &hit.p_error);
+            hit.p = self.to_world.transform_point(&hit.p);
+            hit.ray = ray.clone();
+            hit
+        })
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        self.object.bounds().map(|b| b.transform(&self.to_world))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[allow(unused_imports)]
+    use crate::approx::RelativeEq;
+
+    use crate::predef::materials::GLASS;
+    use crate::primitives::Sphere;
+
+    #[test]
+    fn test_scaled_sphere() {
+        let sphere: Arc<dyn Shadable> = Arc::new(Sphere::new(Point3::default(), 1., GLASS));
+        let transform = Mat4::translate(&Vec3::new(0., 0., -5.)).mul(&Mat4::scale(2., 2., 2.));
+        let instance = Instance::new(sphere, transform).unwrap();
+
+        let ray = Ray::new(Point3::default(), Vec3::new(0., 0., -1.));
+        let hit = instance.intersect(&ray).unwrap();
+        assert_relative_eq!(hit.t, 3., epsilon = 1e-9);
+        assert_relative_eq!(*hit.shading_normal.z(), 1., epsilon = 1e-9);
+        let p = hit.point();
+        assert_relative_eq!(*p.z(), -3., epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_singular_transform() {
+        let sphere: Arc<dyn Shadable> = Arc::new(Sphere::new(Point3::default(), 1., GLASS));
+        assert!(Instance::new(sphere, Mat4::scale(1., 0., 1.)).is_none());
+    }
+}
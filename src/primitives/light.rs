@@ -0,0 +1,485 @@
+use std::f64::consts::PI;
+
+use crate::math::point3::Point3;
+use crate::math::ray::Ray;
+use crate::math::vec3::Vec3;
+use crate::primitives::material::Color;
+use crate::primitives::{LightSample, LightSource};
+use crate::sampler;
+
+const DEFAULT_SAMPLES: u32 = 16;
+
+// Radiometric units are watts, photometric values are converted with the peak
+// luminous efficacy which is exact for monochromatic 555nm light
+pub const LUMENS_PER_WATT: f64 = 683.;
+
+// Sample on a one sided planar emitter with the given area, converted to solid angle
+fn planar_sample(p: &Point3, q: Point3, normal: &Vec3, area: f64, radiance: Color) -> LightSample {
+    let vec = q.sub(p);
+    let dist = vec.mag();
+    let dir = vec.scale(1. / dist);
+    let cos_light = -dir.dot(normal);
+    if cos_light <= 0. {
+        // looking at the back of the light
+        return LightSample {
+            ray: Ray::shadow(*p, dir, dist),
+            dist,
+            radiance: Color::new(0., 0., 0.),
+            pdf: 0.,
+        };
+    }
+    LightSample {
+        ray: Ray::shadow(*p, dir, dist),
+        dist,
+        radiance,
+        pdf: dist * dist / (cos_light * area),
+    }
+}
+
+// Where a ray crosses the front of the plane through `point`
+fn plane_hit(ray: &Ray, point: &Point3, normal: &Vec3) -> Option<(f64, Point3)> {
+    let denom = ray.direction.dot(normal);
+    if denom >= 0. {
+        return None;
+    }
+    let t = point.sub(&ray.origin).dot(normal) / denom;
+    if !ray.in_range(t) {
+        return None;
+    }
+    Some((t, ray.at_t(t)))
+}
+
+// Parallelogram spanned by two perpendicular edges, emits on the side of edge_u x edge_v
+#[derive(Debug)]
+pub struct RectLight {
+    pub corner: Point3,
+    pub edge_u: Vec3,
+    pub edge_v: Vec3,
+    pub col: Color,
+    pub brightness: f64, // emitted radiance in W/(sr m^2)
+    pub samples: u32,
+}
+
+impl RectLight {
+    pub fn new(corner: Point3, edge_u: Vec3, edge_v: Vec3, c: Color, b: f64) -> RectLight {
+        RectLight {
+            corner,
+            edge_u,
+            edge_v,
+            col: c,
+            brightness: b,
+            samples: DEFAULT_SAMPLES,
+        }
+    }
+
+    fn normal(&self) -> Vec3 {
+        self.edge_u.cross(&self.edge_v).norm()
+    }
+
+    fn area(&self) -> f64 {
+        self.edge_u.cross(&self.edge_v).mag()
+    }
+
+    // Total emitted power in watts
+    pub fn set_power(&mut self, watts: f64) -> &mut Self {
+        self.brightness = watts / (PI * self.area());
+        self
+    }
+}
+
+impl LightSource for RectLight {
+    fn trace_light(&self, p: &Point3, u: (f64, f64)) -> LightSample {
+        let q = self
+            .corner
+            .add(&self.edge_u.scale(u.0))
+            .add(&self.edge_v.scale(u.1));
+        planar_sample(p, q, &self.normal(), self.area(), self.col.mult(self.brightness))
+    }
+
+    fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<(f64, Color)> {
+        let (t, q) = plane_hit(ray, &self.corner, &self.normal())?;
+        let local = q.sub(&self.corner);
+        let a = local.dot(&self.edge_u) / self.edge_u.square();
+        let b = local.dot(&self.edge_v) / self.edge_v.square();
+        if (0. ..=1.).contains(&a) && (0. ..=1.).contains(&b) {
+            Some((t, self.col.mult(self.brightness)))
+        } else {
+            None
+        }
+    }
+}
+
+// Flat disk emitting on the side its normal points to
+#[derive(Debug)]
+pub struct DiskLight {
+    pub center: Point3,
+    pub normal: Vec3,
+    pub radius: f64,
+    pub col: Color,
+    pub brightness: f64,
+    pub samples: u32,
+}
+
+impl DiskLight {
+    pub fn new(center: Point3, normal: Vec3, radius: f64, c: Color, b: f64) -> DiskLight {
+        DiskLight {
+            center,
+            normal: normal.norm(),
+            radius,
+            col: c,
+            brightness: b,
+            samples: DEFAULT_SAMPLES,
+        }
+    }
+
+    fn area(&self) -> f64 {
+        PI * self.radius * self.radius
+    }
+
+    // Total emitted power in watts
+    pub fn set_power(&mut self, watts: f64) -> &mut Self {
+        self.brightness = watts / (PI * self.area());
+        self
+    }
+}
+
+impl LightSource for DiskLight {
+    fn trace_light(&self, p: &Point3, u: (f64, f64)) -> LightSample {
+        let (x, y) = sampler::concentric_disk(u);
+        let local = Vec3::new(x * self.radius, y * self.radius, 0.);
+        let q = self.center.add(&sampler::to_world(&local, &self.normal));
+        planar_sample(p, q, &self.normal, self.area(), self.col.mult(self.brightness))
+    }
+
+    fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<(f64, Color)> {
+        let (t, q) = plane_hit(ray, &self.center, &self.normal)?;
+        if q.sub(&self.center).square() <= self.radius * self.radius {
+            Some((t, self.col.mult(self.brightness)))
+        } else {
+            None
+        }
+    }
+}
+
+// Sphere emitting outward, sampled over the cone it subtends
+#[derive(Debug)]
+pub struct SphereLight {
+    pub center: Point3,
+    pub radius: f64,
+    pub col: Color,
+    pub brightness: f64,
+    pub samples: u32,
+}
+
+impl SphereLight {
+    pub fn new(center: Point3, radius: f64, c: Color, b: f64) -> SphereLight {
+        SphereLight {
+            center,
+            radius,
+            col: c,
+            brightness: b,
+            samples: DEFAULT_SAMPLES,
+        }
+    }
+
+    // Total emitted power in watts
+    pub fn set_power(&mut self, watts: f64) -> &mut Self {
+        self.brightness = watts / (4. * PI * PI * self.radius * self.radius);
+        self
+    }
+}
+
+impl LightSource for SphereLight {
+    fn trace_light(&self, p: &Point3, u: (f64, f64)) -> LightSample {
+        let to_center = self.center.sub(p);
+        let dist2 = to_center.square();
+        let r2 = self.radius * self.radius;
+        if dist2 <= r2 {
+            // inside the light, nothing to sample
+            return LightSample {
+                ray: Ray::new(*p, Vec3::new(0., 1., 0.)),
+                dist: 0.,
+                radiance: Color::new(0., 0., 0.),
+                pdf: 0.,
+            };
+        }
+        let sin2_max = r2 / dist2;
+        let cos_max = (1. - sin2_max).sqrt();
+        // 1 - cos_max without cancellation for small, distant spheres
+        let one_minus_cos = sin2_max / (1. + cos_max);
+
+        let axis = to_center.scale(1. / dist2.sqrt());
+        let local = sampler::uniform_cone(u, 1. - one_minus_cos);
+        let dir = sampler::to_world(&local, &axis).norm();
+
+        // nearest hit of the sampled direction with the sphere
+        let b = dir.dot(&to_center);
+        let t = b - (b * b - (dist2 - r2)).max(0.).sqrt();
+        LightSample {
+            ray: Ray::shadow(*p, dir, t),
+            dist: t,
+            radiance: self.col.mult(self.brightness),
+            pdf: 1. / (2. * PI * one_minus_cos),
+        }
+    }
+
+    fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<(f64, Color)> {
+        let oc = ray.origin.sub(&self.center);
+        let a = ray.direction.square();
+        let b = oc.dot(&ray.direction);
+        let c = oc.square() - self.radius * self.radius;
+        let disc = b * b - a * c;
+        if disc < 0. {
+            return None;
+        }
+        let t = (-b - disc.sqrt()) / a;
+        if ray.in_range(t) {
+            Some((t, self.col.mult(self.brightness)))
+        } else {
+            None
+        }
+    }
+}
+
+// Light from very far away such as the sun. With a nonzero angular diameter it covers
+// a small cone of directions and casts slightly soft shadows.
+#[derive(Debug)]
+pub struct DirectionalLight {
+    pub direction: Vec3, // the way the light travels
+    pub col: Color,
+    pub brightness: f64, // irradiance in W/m^2 on a surface facing the light
+    pub angular_diameter: f64, // degrees, 0 for perfectly hard shadows
+    pub samples: u32,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, c: Color, b: f64) -> DirectionalLight {
+        DirectionalLight {
+            direction: direction.norm(),
+            col: c,
+            brightness: b,
+            angular_diameter: 0.,
+            samples: DEFAULT_SAMPLES,
+        }
+    }
+
+    pub fn with_angular_diameter(mut self, degrees: f64) -> Self {
+        self.angular_diameter = degrees;
+        self
+    }
+}
+
+impl LightSource for DirectionalLight {
+    fn trace_light(&self, p: &Point3, u: (f64, f64)) -> LightSample {
+        let to_light = self.direction.scale(-1.);
+        let irradiance = self.col.mult(self.brightness);
+        if self.angular_diameter <= 0. {
+            return LightSample {
+                ray: Ray::shadow(*p, to_light, f64::INFINITY),
+                dist: f64::INFINITY,
+                radiance: irradiance,
+                pdf: 1.,
+            };
+        }
+        // spread the irradiance evenly over the cone the light covers
+        let cos_max = (self.angular_diameter.to_radians() / 2.).cos();
+        let solid_angle = 2. * PI * (1. - cos_max);
+        let dir = sampler::to_world(&sampler::uniform_cone(u, cos_max), &to_light).norm();
+        LightSample {
+            ray: Ray::shadow(*p, dir, f64::INFINITY),
+            dist: f64::INFINITY,
+            radiance: irradiance.mult(1. / solid_angle),
+            pdf: 1. / solid_angle,
+        }
+    }
+
+    fn samples(&self) -> u32 {
+        if self.angular_diameter <= 0. {
+            1
+        } else {
+            self.samples
+        }
+    }
+}
+
+// Point light restricted to a cone, fading out smoothly between the inner and outer angle
+#[derive(Debug)]
+pub struct SpotLight {
+    pub origin: Point3,
+    pub direction: Vec3,
+    pub col: Color,
+    pub brightness: f64, // radiant intensity in W/sr along the axis
+    pub inner_angle: f64, // degrees from the axis with full brightness
+    pub outer_angle: f64, // degrees from the axis beyond which there is no light
+}
+
+impl SpotLight {
+    pub fn new(o: Point3, direction: Vec3, c: Color, b: f64) -> SpotLight {
+        SpotLight {
+            origin: o,
+            direction: direction.norm(),
+            col: c,
+            brightness: b,
+            inner_angle: 20.,
+            outer_angle: 30.,
+        }
+    }
+
+    pub fn set_cone(&mut self, inner: f64, outer: f64) -> &mut Self {
+        self.inner_angle = inner.min(outer);
+        self.outer_angle = outer;
+        self
+    }
+
+    // Total emitted power in watts, approximating the falloff region as half lit
+    pub fn set_power(&mut self, watts: f64) -> &mut Self {
+        let cos_inner = self.inner_angle.to_radians().cos();
+        let cos_outer = self.outer_angle.to_radians().cos();
+        self.brightness = watts / (2. * PI * (1. - 0.5 * (cos_inner + cos_outer)));
+        self
+    }
+
+    // 1 inside the inner cone, 0 outside the outer one and smoothstep in between
+    fn falloff(&self, dir: &Vec3) -> f64 {
+        let cos = dir.dot(&self.direction);
+        let cos_inner = self.inner_angle.to_radians().cos();
+        let cos_outer = self.outer_angle.to_radians().cos();
+        if cos >= cos_inner {
+            return 1.;
+        }
+        if cos <= cos_outer {
+            return 0.;
+        }
+        let x = (cos - cos_outer) / (cos_inner - cos_outer);
+        x * x * (3. - 2. * x)
+    }
+}
+
+impl LightSource for SpotLight {
+    fn trace_light(&self, p: &Point3, _u: (f64, f64)) -> LightSample {
+        let vec = self.origin.sub(p);
+        let t = vec.mag();
+        let dir = vec.norm();
+        let falloff = self.falloff(&dir.scale(-1.));
+        LightSample {
+            ray: Ray::shadow(*p, dir, t),
+            dist: t,
+            radiance: self.col.mult(self.brightness * falloff / (t * t)),
+            pdf: 1.,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[allow(unused_imports)]
+    use crate::approx::RelativeEq;
+    use crate::sampler::Sampler;
+
+    // Monte Carlo estimate of the irradiance at p on a surface facing +y
+    fn irradiance(light: &dyn LightSource, p: &Point3) -> f64 {
+        let mut sampler = Sampler::new(7);
+        let n = 20000;
+        let normal = Vec3::new(0., 1., 0.);
+        let mut sum = 0.;
+        for _ in 0..n {
+            let sample = light.trace_light(p, sampler.next_2d());
+            let cos = sample.ray.direction.dot(&normal);
+            if sample.pdf > 0. && cos > 0. {
+                sum += sample.radiance.red * cos / sample.pdf;
+            }
+        }
+        sum / f64::from(n)
+    }
+
+    #[test]
+    fn test_irradiance_matches_closed_form() {
+        let p = Point3::new(0., 0., 0.);
+        let white = Color::new(1., 1., 1.);
+
+        // on the axis of a disk facing down: pi r^2 / (r^2 + h^2)
+        let disk = DiskLight::new(Point3::new(0., 2., 0.), Vec3::new(0., -1., 0.), 1., white, 1.);
+        assert_relative_eq!(irradiance(&disk, &p), PI / 5., max_relative = 0.02);
+
+        // a sphere fully above the horizon: pi (r / d)^2
+        let sphere = SphereLight::new(Point3::new(0., 4., 0.), 1., white, 1.);
+        assert_relative_eq!(irradiance(&sphere, &p), PI / 16., max_relative = 0.02);
+
+        // a square the same size as the disk by area gives about the same result
+        let side = PI.sqrt();
+        let rect = RectLight::new(
+            Point3::new(-side / 2., 2., -side / 2.),
+            Vec3::new(side, 0., 0.),
+            Vec3::new(0., 0., side),
+            white,
+            1.,
+        );
+        assert_relative_eq!(irradiance(&rect, &p), PI / 5., max_relative = 0.05);
+    }
+
+    #[test]
+    fn test_sun_irradiance() {
+        let p = Point3::new(0., 0., 0.);
+        let white = Color::new(1., 1., 1.);
+        let dir = Vec3::new(1., -1., 0.);
+        // a small sun gives nearly the same irradiance as a hard one
+        let hard = DirectionalLight::new(dir, white, 2.);
+        let soft = DirectionalLight::new(dir, white, 2.).with_angular_diameter(5.);
+        let expected = 2. * (PI / 4.).cos();
+        assert_relative_eq!(irradiance(&hard, &p), expected, max_relative = 1e-9);
+        assert_relative_eq!(irradiance(&soft, &p), expected, max_relative = 0.01);
+    }
+
+    #[test]
+    fn test_spot_falloff() {
+        let mut spot = SpotLight::new(Point3::new(0., 2., 0.), Vec3::new(0., -1., 0.), Color::new(1., 1., 1.), 4.);
+        spot.set_cone(20., 40.);
+        let at = |x: f64| spot.trace_light(&Point3::new(x, 0., 0.), (0., 0.)).radiance.red;
+        assert_relative_eq!(at(0.), 1.);
+        // 30 degrees off the axis, between the two cones
+        let edge = at(2. * 30f64.to_radians().tan());
+        assert!(edge > 0. && edge < 1.);
+        assert_eq!(at(2. * 45f64.to_radians().tan()), 0.);
+    }
+
+    #[test]
+    fn test_samples_lie_on_the_light() {
+        let p = Point3::new(0.3, 0., 0.2);
+        let white = Color::new(1., 1., 1.);
+        let lights: Vec<Box<dyn LightSource>> = vec![
+            Box::new(RectLight::new(
+                Point3::new(-1., 2., -1.),
+                Vec3::new(2., 0., 0.),
+                Vec3::new(0., 0., 2.),
+                white,
+                1.,
+            )),
+            Box::new(DiskLight::new(Point3::new(0., 2., 0.), Vec3::new(0., -1., 0.), 1., white, 1.)),
+            Box::new(SphereLight::new(Point3::new(0., 4., 0.), 1., white, 1.)),
+        ];
+        let mut sampler = Sampler::new(3);
+        for light in lights.iter() {
+            for _ in 0..16 {
+                let sample = light.trace_light(&p, sampler.next_2d());
+                let (mut ray, dist) = (sample.ray, sample.dist);
+                assert!(ray.t_max < dist);
+                ray.t_max = f64::INFINITY;
+                let (t, _) = light.intersect(&ray).unwrap();
+                assert_relative_eq!(t, dist, epsilon = 1e-9);
+            }
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use std::ops::Deref;
+
 use image::Rgb;
 
 #[derive(Debug, Copy, Clone)]
@@ -24,9 +26,9 @@ impl Color {
 
     pub fn clamp(&self) -> Color {
         Color {
-            red: self.red.max(0.0).min(1.0),
-            green: self.green.max(0.0).min(1.0),
-            blue: self.blue.max(0.0).min(1.0),
+            red: self.red.clamp(0.0, 1.0),
+            green: self.green.clamp(0.0, 1.0),
+            blue: self.blue.clamp(0.0, 1.0),
         }
     }
 
@@ -39,6 +41,23 @@ impl Color {
         }
     }
 
+    // Relative luminance of linear Rec. 709 primaries
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.red + 0.7152 * self.green + 0.0722 * self.blue
+    }
+
+    pub fn mult_col(&self, other: &Color) -> Color {
+        Color {
+            red: self.red * other.red,
+            green: self.green * other.green,
+            blue: self.blue * other.blue,
+        }
+    }
+
+    pub fn max_component(&self) -> f64 {
+        self.red.max(self.green).max(self.blue)
+    }
+
     pub fn mix(&self, other: &Color, fact: f64) -> Color {
         Color {
             red: (self.red * fact + other.red * (1.0 - fact)),
@@ -47,11 +66,13 @@ impl Color {
         }
     }
 
+    // Quantizes already encoded values, rounding to the nearest level
     pub fn to_rgb(&self) -> Rgb<u8> {
+        let c = self.clamp();
         Rgb([
-            (self.red * 255.) as u8,
-            (self.green * 255.) as u8,
-            (self.blue * 255.) as u8,
+            (c.red * 255.).round() as u8,
+            (c.green * 255.).round() as u8,
+            (c.blue * 255.).round() as u8,
         ])
     }
 }
@@ -73,9 +94,96 @@ pub enum Material {
         spec_col: Color,
         refr_col: Color,
         ior: f64,
+        priority: u32, // wins over lower priorities where volumes overlap
     },
 }
 
+// A refractive volume a ray is travelling through
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Medium {
+    pub ior: f64,
+    pub priority: u32,
+}
+
+// The medium that decides the index of refraction, the highest priority and
+// the innermost of equal priorities. None is empty space with an index of 1
+pub fn dominant_medium(media: &[Medium]) -> Option<Medium> {
+    media.iter().copied().max_by_key(|m| m.priority)
+}
+
+// Nesting depth up to which a ray keeps its volumes inline, deeper stacks move to the heap
+const INLINE_MEDIA: usize = 4;
+
+// The volumes a ray is inside of, innermost last. Shallow nesting is stored inline
+// so cloning a ray does not allocate in the common case
+#[derive(Debug, Clone)]
+pub struct MediumStack(MediumStorage);
+
+#[derive(Debug, Clone)]
+enum MediumStorage {
+    Inline([Medium; INLINE_MEDIA], usize),
+    Heap(Vec<Medium>),
+}
+
+impl MediumStack {
+    pub fn new() -> MediumStack {
+        MediumStack(MediumStorage::Inline([Medium { ior: 1., priority: 0 }; INLINE_MEDIA], 0))
+    }
+
+    pub fn push(&mut self, medium: Medium) {
+        match &mut self.0 {
+            MediumStorage::Inline(media, len) if *len < INLINE_MEDIA => {
+                media[*len] = medium;
+                *len += 1;
+            }
+            MediumStorage::Inline(media, _) => {
+                let mut heap = media.to_vec();
+                heap.push(medium);
+                self.0 = MediumStorage::Heap(heap);
+            }
+            MediumStorage::Heap(media) => media.push(medium),
+        }
+    }
+
+    // Leaves the innermost occurrence of medium
+    pub fn remove(&mut self, medium: &Medium) {
+        if let Some(i) = self.iter().rposition(|m| m == medium) {
+            match &mut self.0 {
+                MediumStorage::Inline(media, len) => {
+                    media.copy_within(i + 1..*len, i);
+                    *len -= 1;
+                }
+                MediumStorage::Heap(media) => {
+                    media.remove(i);
+                }
+            }
+        }
+    }
+}
+
+impl Default for MediumStack {
+    fn default() -> Self {
+        MediumStack::new()
+    }
+}
+
+impl Deref for MediumStack {
+    type Target = [Medium];
+
+    fn deref(&self) -> &[Medium] {
+        match &self.0 {
+            MediumStorage::Inline(media, len) => &media[..*len],
+            MediumStorage::Heap(media) => media,
+        }
+    }
+}
+
+impl PartialEq for MediumStack {
+    fn eq(&self, other: &MediumStack) -> bool {
+        **self == **other
+    }
+}
+
 impl Material {
     pub fn diffuse(col : Color) -> Self {
         Material::Diffuse {
@@ -101,7 +209,29 @@ impl Material {
         Material::Refractive {
             spec_col: scol,
             refr_col: rcol,
-            ior: ior,
+            ior,
+            priority: 0,
+        }
+    }
+
+    // Same material with a different priority, only refractive materials have one
+    pub fn with_priority(self, p: u32) -> Self {
+        match self {
+            Material::Refractive { spec_col, refr_col, ior, .. } => Material::Refractive {
+                spec_col,
+                refr_col,
+                ior,
+                priority: p,
+            },
+            other => other,
+        }
+    }
+
+    // Volume enclosed by surfaces of this material
+    pub fn medium(&self) -> Option<Medium> {
+        match *self {
+            Material::Refractive { ior, priority, .. } => Some(Medium { ior, priority }),
+            _ => None,
         }
     }
 
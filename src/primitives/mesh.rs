@@ -0,0 +1,288 @@
+use crate::accel::bvh::Bvh;
+use crate::math::aabb::Aabb;
+use crate::math::gamma;
+use crate::math::point3::Point3;
+use crate::math::ray::Ray;
+use crate::math::vec3::Vec3;
+
+use super::material::Material;
+use super::Intersection;
+use super::Shadable;
+
+// Möller–Trumbore, returns (t, b1, b2) where the hit point is b0*p0 + b1*p1 + b2*p2
+fn intersect_triangle(p0: &Point3, p1: &Point3, p2: &Point3, ray: &Ray) -> Option<(f64, f64, f64)> {
+    let e1 = p1.sub(p0);
+    let e2 = p2.sub(p0);
+    let pvec = ray.direction().cross(&e2);
+    let det = e1.dot(&pvec);
+    // det scales with the edge lengths, any fixed threshold would drop small triangles
+    if det == 0. {
+        return None; // parallel to the triangle
+    }
+    let inv_det = det.recip();
+
+    let tvec = ray.origin().sub(p0);
+    let b1 = tvec.dot(&pvec) * inv_det;
+    if !(0. ..=1.).contains(&b1) {
+        return None;
+    }
+
+    let qvec = tvec.cross(&e1);
+    let b2 = ray.direction().dot(&qvec) * inv_det;
+    if b2 < 0. || b1 + b2 > 1. {
+        return None;
+    }
+
+    let t = e2.dot(&qvec) * inv_det;
+    if ray.in_range(t) {
+        Some((t, b1, b2))
+    } else {
+        None
+    }
+}
+
+// Hit point from barycentrics, which is more accurate than stepping along the ray,
+// together with its error bound
+fn barycentric_point(p0: &Point3, p1: &Point3, p2: &Point3, b0: f64, b1: f64, b2: f64) -> (Point3, Vec3) {
+    let (w0, w1, w2) = (p0.as_vec3().scale(b0), p1.as_vec3().scale(b1), p2.as_vec3().scale(b2));
+    let p = w0.add(&w1).add(&w2).as_point3();
+    let p_error = w0.abs().add(&w1.abs()).add(&w2.abs()).scale(gamma(7));
+    (p, p_error)
+}
+
+#[derive(Debug)]
+pub struct Triangle {
+    points: [Point3; 3],
+    material: Material,
+}
+
+impl Triangle {
+    // Counter-clockwise winding faces the normal towards the viewer
+    pub fn new(p0: Point3, p1: Point3, p2: Point3, mat: Material) -> Triangle {
+        Triangle {
+            points: [p0, p1, p2],
+            material: mat,
+        }
+    }
+}
+
+impl Shadable for Triangle {
+    fn normal(&self, _p: &Point3) -> Option<Vec3> {
+        let [p0, p1, p2] = &self.points;
+        Some(p1.sub(p0).cross(&p2.sub(p0)).norm())
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Intersection<'_>> {
+        let [p0, p1, p2] = &self.points;
+        let (t, b1, b2) = intersect_triangle(p0, p1, p2, ray)?;
+        let b0 = 1. - b1 - b2;
+        let (p, p_error) = barycentric_point(p0, p1, p2, b0, b1, b2);
+        Some(
+            Intersection::new(self.normal(p0).unwrap(), &self.material, ray.clone(), t)
+                .with_barycentric(b0, b1, b2)
+                .with_point(p, p_error),
+        )
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        let [p0, p1, p2] = &self.points;
+        Some(Aabb::new(p0, p1).union_point(p2))
+    }
+}
+
+// Indexed triangles sharing vertex buffers, normals and uvs are either empty or one per position
+#[derive(Debug)]
+pub struct TriangleMesh {
+    positions: Vec<Point3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f64, f64)>,
+    indices: Vec<[usize; 3]>,
+    material: Material,
+    bvh: Bvh,
+}
+
+impl TriangleMesh {
+    pub fn new(
+        positions: Vec<Point3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<(f64, f64)>,
+        indices: Vec<[usize; 3]>,
+        mat: Material,
+    ) -> TriangleMesh {
+        assert!(normals.is_empty() || normals.len() == positions.len());
+        assert!(uvs.is_empty() || uvs.len() == positions.len());
+        assert!(indices.iter().flatten().all(|&i| i < positions.len()));
+
+        let bounds: Vec<Aabb> = indices
+            .iter()
+            .map(|[a, b, c]| Aabb::new(&positions[*a], &positions[*b]).union_point(&positions[*c]))
+            .collect();
+        TriangleMesh {
+            bvh: Bvh::build(&bounds),
+            positions,
+            normals,
+            uvs,
+            indices,
+            material: mat,
+        }
+    }
+
+    pub fn num_triangles(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    // Area weighted vertex normals for smooth shading of meshes without their own
+    pub fn compute_vertex_normals(&mut self) {
+        let mut normals = vec![Vec3::new(0., 0., 0.); self.positions.len()];
+        for [a, b, c] in self.indices.iter() {
+            let p0 = &self.positions[*a];
+            let face = self.positions[*b].sub(p0).cross(&self.positions[*c].sub(p0));
+            for i in [a, b, c].iter() {
+                normals[**i].add_mut(&face);
+            }
+        }
+        self.normals = normals
+            .iter()
+            .map(|n| if n.square() > 0. { n.norm() } else { *n })
+            .collect();
+    }
+
+    fn intersect_face(&self, face: usize, ray: &Ray) -> Option<Intersection<'_>> {
+        let [a, b, c] = self.indices[face];
+        let (p0, p1, p2) = (&self.positions[a], &self.positions[b], &self.positions[c]);
+        let (t, b1, b2) = intersect_triangle(p0, p1, p2, ray)?;
+        let b0 = 1. - b1 - b2;
+        let (p, p_error) = barycentric_point(p0, p1, p2, b0, b1, b2);
+
+        let face_normal = p1.sub(p0).cross(&p2.sub(p0)).norm();
+        let normal = if self.normals.is_empty() {
+            face_normal
+        } else {
+            self.normals[a]
+                .scale(b0)
+                .add(&self.normals[b].scale(b1))
+                .add(&self.normals[c].scale(b2))
+                .norm()
+        };
+
+        let hit = Intersection::new(normal, &self.material, ray.clone(), t)
+            .with_barycentric(b0, b1, b2)
+            .with_point(p, p_error)
+            .with_geometric_normal(face_normal);
+        if self.uvs.is_empty() {
+            Some(hit)
+        } else {
+            let (uv0, uv1, uv2) = (self.uvs[a], self.uvs[b], self.uvs[c]);
+            Some(hit.with_uv(
+                b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0,
+                b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1,
+            ))
+        }
+    }
+}
+
+impl Shadable for TriangleMesh {
+    // The face is unknown from a point alone
+    fn normal(&self, _p: &Point3) -> Option<Vec3> {
+        None
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<Intersection<'_>> {
+        self.bvh.intersect(ray, |face, ray| self.intersect_face(face, ray))
+    }
+
+    fn occludes(&self, ray: &Ray) -> bool {
+        self.bvh.any_hit(ray, |face| {
+            let [a, b, c] = self.indices[face];
+            intersect_triangle(&self.positions[a], &self.positions[b], &self.positions[c], ray).is_some()
+        })
+    }
+
+    fn bounds(&self) -> Option<Aabb> {
+        if self.bvh.is_empty() {
+            None
+        } else {
+            Some(self.bvh.bounds())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[allow(unused_imports)]
+    use crate::approx::RelativeEq;
+
+    use crate::predef::materials::GLASS;
+
+    fn quad() -> TriangleMesh {
+        let positions = vec![
+            Point3::new(-1., -1., 0.),
+            Point3::new(1., -1., 0.),
+            Point3::new(1., 1., 0.),
+            Point3::new(-1., 1., 0.),
+        ];
+        let normals = vec![
+            Vec3::new(-1., 0., 1.).norm(),
+            Vec3::new(1., 0., 1.).norm(),
+            Vec3::new(1., 0., 1.).norm(),
+            Vec3::new(-1., 0., 1.).norm(),
+        ];
+        TriangleMesh::new(positions, normals, Vec::new(), vec![[0, 1, 2], [0, 2, 3]], GLASS)
+    }
+
+    #[test]
+    fn test_triangle_barycentric() {
+        let tri = Triangle::new(
+            Point3::new(0., 0., -1.),
+            Point3::new(1., 0., -1.),
+            Point3::new(0., 1., -1.),
+            GLASS,
+        );
+        let ray = Ray::new(Point3::new(0.25, 0.5, 0.), Vec3::new(0., 0., -1.));
+        let hit = tri.intersect(&ray).unwrap();
+        let (b0, b1, b2) = hit.barycentric.unwrap();
+        assert_relative_eq!(hit.t, 1.);
+        assert_relative_eq!(b0, 0.25);
+        assert_relative_eq!(b1, 0.25);
+        assert_relative_eq!(b2, 0.5);
+        assert_relative_eq!(*hit.shading_normal.z(), 1.);
+
+        let miss = Ray::new(Point3::new(0.75, 0.5, 0.), Vec3::new(0., 0., -1.));
+        assert!(tri.intersect(&miss).is_none());
+    }
+
+    #[test]
+    fn test_tiny_triangle() {
+        let size = 1e-7;
+        let tri = Triangle::new(
+            Point3::new(0., 0., -1.),
+            Point3::new(size, 0., -1.),
+            Point3::new(0., size, -1.),
+            GLASS,
+        );
+        let ray = Ray::new(Point3::new(0.25 * size, 0.5 * size, 0.), Vec3::new(0., 0., -1.));
+        let hit = tri.intersect(&ray).unwrap();
+        assert_relative_eq!(hit.t, 1.);
+        let (_, b1, b2) = hit.barycentric.unwrap();
+        assert_relative_eq!(b1, 0.25, epsilon = 1e-6);
+        assert_relative_eq!(b2, 0.5, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn test_mesh_smooth_normal() {
+        let mesh = quad();
+        let ray = Ray::new(Point3::new(0., 0.5, 2.), Vec3::new(0., 0., -1.));
+        let hit = mesh.intersect(&ray).unwrap();
+        assert_relative_eq!(hit.t, 2.);
+        assert_relative_eq!(*hit.shading_normal.x(), 0., epsilon = 1e-9);
+
+        let ray = Ray::new(Point3::new(1., 0., 2.), Vec3::new(0., 0., -1.));
+        let hit = mesh.intersect(&ray).unwrap();
+        assert_relative_eq!(*hit.shading_normal.x(), 0.5_f64.sqrt(), epsilon = 1e-9);
+    }
+}
@@ -2,12 +2,14 @@ use std::cmp::Ordering;
 use std::f64;
 
 
+use crate::math::aabb::Aabb;
+use crate::math::gamma;
 use crate::math::point3::Point3;
 use crate::math::ray::Ray;
 use crate::math::vec3::Vec3;
 
 use material::Color;
-use material::Material;
+use material::{dominant_medium, Material, Medium, MediumStack};
 
 // Primitives
 #[derive(Debug)]
@@ -48,7 +50,7 @@ impl Plane {
 pub struct PointLight {
     pub origin: Point3,
     pub col: Color,
-    pub brightness: f64,
+    pub brightness: f64, // radiant intensity in W/sr
 }
 
 impl PointLight {
@@ -64,6 +66,12 @@ impl PointLight {
         self.origin = *p;
         self
     }
+
+    // Total emitted power in watts, spread evenly over the sphere
+    pub fn set_power(&mut self, watts: f64) -> &mut Self {
+        self.brightness = watts / (4. * f64::consts::PI);
+        self
+    }
 }
 
 impl Default for PointLight {
@@ -78,27 +86,95 @@ impl Default for PointLight {
 
 #[derive(Debug)]
 pub struct Intersection<'a> {
-    pub normal: Vec3,
+    // Both normals point out of the surface whichever side the ray came from
+    pub shading_normal: Vec3, // interpolated or otherwise smoothed, for lighting
+    pub geometric_normal: Vec3, // of the actual surface
+    pub front_face: bool, // the ray arrived from the side the geometric normal points to
     pub material: &'a Material,
-    pub ray: Ray,
+    pub ray: Ray, // the ray that found the hit
+    pub t: f64,   // parametric distance along it
+    pub p: Point3,
+    pub p_error: Vec3, // bound on the absolute rounding error in each coordinate of p
+    pub barycentric: Option<(f64, f64, f64)>, // set by triangle primitives
+    pub uv: Option<(f64, f64)>, // surface texture coordinates, if the primitive has them
     //pub next_bounce: Box<Intersection<'a>>, // next bounce in a series of rays
 }
 
 impl<'a> Intersection<'a> {
-    pub fn new(n: Vec3, mat: &'a Material, r: Ray) -> Intersection<'a> {
+    // Primitives that can compute the point more accurately than along the ray
+    // replace it with with_point
+    pub fn new(n: Vec3, mat: &'a Material, r: Ray, t: f64) -> Intersection<'a> {
+        let p = r.at_t(t);
+        let p_error = r.origin.abs().add(&r.direction.scale(t).abs()).scale(gamma(3));
         Intersection {
-            normal: n,
+            shading_normal: n,
+            geometric_normal: n,
+            front_face: r.direction.dot(&n) < 0.,
             material: mat,
             ray: r,
+            t,
+            p,
+            p_error,
+            barycentric: None,
+            uv: None,
         }
     }
 
+    pub fn with_barycentric(mut self, b0: f64, b1: f64, b2: f64) -> Self {
+        self.barycentric = Some((b0, b1, b2));
+        self
+    }
+
+    pub fn with_uv(mut self, u: f64, v: f64) -> Self {
+        self.uv = Some((u, v));
+        self
+    }
+
+    pub fn with_point(mut self, p: Point3, p_error: Vec3) -> Self {
+        self.p = p;
+        self.p_error = p_error;
+        self
+    }
+
+    pub fn with_geometric_normal(mut self, n: Vec3) -> Self {
+        self.geometric_normal = n;
+        self.front_face = self.ray.direction.dot(&n) < 0.;
+        self
+    }
+
     pub fn point(&self) -> Point3 {
-        self.ray.as_point3()
+        self.p
+    }
+
+    // The hit point pushed along the geometric normal past its error bound, to the side
+    // `dir` points to. Rays leaving from there can't find the surface again.
+    pub fn offset_point(&self, dir: &Vec3) -> Point3 {
+        let n = self.geometric_normal;
+        let dist = n.abs().dot(&self.p_error);
+        let offset = if dir.dot(&n) < 0. { n.scale(-dist) } else { n.scale(dist) };
+        let p = self.p.add(&offset);
+        // round away from the surface so adding the offset can't undo it
+        let away = |x: f64, o: f64| {
+            if o > 0. {
+                x.next_up()
+            } else if o < 0. {
+                x.next_down()
+            } else {
+                x
+            }
+        };
+        Point3::new(away(*p.x(), *offset.x()), away(*p.y(), *offset.y()), away(*p.z(), *offset.z()))
+    }
+
+    // Ray leaving the surface in direction dir
+    pub fn spawn_ray(&self, dir: Vec3) -> Ray {
+        Ray::new(self.offset_point(&dir), dir)
     }
 
-    pub fn biased_point(&self,dir: &Vec3) -> Point3 {
-        self.ray.as_point3().add(&dir.scale(1e-4))
+    // Visibility test toward a point sampled on a light
+    pub fn shadow_ray(&self, sample: &LightSample) -> Ray {
+        let dir = sample.ray.direction;
+        Ray::shadow(self.offset_point(&dir), dir, sample.dist)
     }
 
     pub fn mat(&self) -> Material {
@@ -106,27 +182,69 @@ impl<'a> Intersection<'a> {
     }
 
     pub fn reflect(&self) -> Ray {
-        let ang = self.ray.direction.dot(&self.normal);
-        let dir = self.ray.direction.sub(&self.normal.scale(2.*(ang)));
-        Ray::new(self.point(),dir,1.)
+        let ang = self.ray.direction.dot(&self.shading_normal);
+        let dir = self.ray.direction.sub(&self.shading_normal.scale(2.*(ang)));
+        let mut refl_ray = self.spawn_ray(dir);
+        refl_ray.media = self.ray.media.clone();
+        refl_ray
     }
 
-    pub fn refract(&self) -> Option<Ray> {
-        if let Material::Refractive { spec_col, refr_col, ior } = &self.mat() {
-            let mut cos = self.ray.direction.dot(&self.normal);
-            let n1 : f64;
-            let n2 : f64;
-            let normal: Vec3;
-            if cos <= 0. { // entering a medium (incident to normal direction > 90 deg)
-                n2 = *ior;
-                n1 = 1.; // ior of water, TODO add to material
-                cos = self.ray.direction.scale(-1.).dot(&self.normal);
-                normal = self.normal.scale(-1.);
-            } else { // exiting a medium
-                n1 = *ior;
-                n2 = 1.;
-                normal = self.normal;
+    // Shading normal on the side the ray arrives from
+    pub fn facing_normal(&self) -> Vec3 {
+        if self.front_face {
+            self.shading_normal
+        } else {
+            self.shading_normal.scale(-1.)
+        }
+    }
+
+    // Media the ray is inside of once it crosses this surface
+    pub fn media_after(&self) -> MediumStack {
+        let mut media = self.ray.media.clone();
+        if let Some(medium) = self.material.medium() {
+            if self.front_face {
+                media.push(medium);
+            } else {
+                media.remove(&medium);
             }
+        }
+        media
+    }
+
+    // A refractive surface inside a volume of higher priority, light passes it unchanged
+    pub fn is_false_hit(&self) -> bool {
+        match self.material.medium() {
+            Some(medium) if self.front_face || self.ray.media.contains(&medium) => {
+                dominant_medium(&self.ray.media) == dominant_medium(&self.media_after())
+            }
+            _ => false,
+        }
+    }
+
+    // Continues the ray on the other side of the surface
+    pub fn pass_through(&self) -> Ray {
+        let mut ray = self.spawn_ray(self.ray.direction);
+        ray.media = self.media_after();
+        ray
+    }
+
+    // Incident cosine and the indices of refraction on the incoming (n1) and outgoing (n2) side
+    fn crossing(&self, medium: &Medium) -> (f64, f64, f64) {
+        let cos = self.ray.direction.dot(&self.shading_normal).abs();
+        let ior_of = |m: Option<Medium>| m.map_or(1., |m| m.ior);
+        let current = ior_of(dominant_medium(&self.ray.media));
+        if self.front_face || self.ray.media.contains(medium) {
+            (cos, current, ior_of(dominant_medium(&self.media_after())))
+        } else {
+            // leaving a volume the ray was never seen entering, e.g. the camera started inside
+            (cos, medium.ior, current)
+        }
+    }
+
+    pub fn refract(&self) -> Option<Ray> {
+        if let Some(medium) = self.material.medium() {
+            let (cos, n1, n2) = self.crossing(&medium);
+            let normal = self.facing_normal();
 
             let ior_frac = n1/n2;
             let incident_comp = ior_frac;
@@ -137,36 +255,55 @@ impl<'a> Intersection<'a> {
             }
             let normal_comp = ior_frac*cos - (1. - sin_theta_t).sqrt();
 
-            let dir = self.ray.direction.scale(incident_comp).add(&self.normal.scale(normal_comp)).norm();
-            Some(Ray::new(self.biased_point(&normal),dir,1.))
+            let dir = self.ray.direction.scale(incident_comp).add(&normal.scale(normal_comp)).norm();
+            let mut refr_ray = self.spawn_ray(dir);
+            refr_ray.media = self.media_after();
+            Some(refr_ray)
         } else {
             None
         }
     }
 
-    pub fn add_bias(&mut self) {
-        //self.ray.origin = self.ray.origin.add(&self.normal.scale(1e-7));
-        self.ray.t -= 1e-7;
+    // Fraction of the light reflected, exact Fresnel equations for unpolarized light.
+    // 1 under total internal reflection and for materials that don't refract
+    pub fn fresnel(&self) -> f64 {
+        if let Some(medium) = self.material.medium() {
+            let (cos_i, n1, n2) = self.crossing(&medium);
+            let sin_t = n1 / n2 * (1. - cos_i * cos_i).max(0.).sqrt();
+            if sin_t >= 1. {
+                return 1.;
+            }
+            let cos_t = (1. - sin_t * sin_t).sqrt();
+            let r_par = (n2 * cos_i - n1 * cos_t) / (n2 * cos_i + n1 * cos_t);
+            let r_perp = (n1 * cos_i - n2 * cos_t) / (n1 * cos_i + n2 * cos_t);
+            (r_par * r_par + r_perp * r_perp) / 2.
+        } else {
+            1.
+        }
     }
-
 }
 
 impl<'a> PartialOrd for Intersection<'a> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.ray.t.partial_cmp(&other.ray.t)
+        self.t.partial_cmp(&other.t)
     }
 }
 
 impl<'a> PartialEq for Intersection<'a> {
     fn eq(&self, other: &Self) -> bool {
-        self.ray.t == other.ray.t
+        self.t == other.t
     }
 }
 
 // Traits
-pub trait Shadable: std::fmt::Debug {
+pub trait Shadable: std::fmt::Debug + Send + Sync {
     fn normal(&self, p: &Point3) -> Option<Vec3>;
-    fn intersect(&self, ray: &Ray) -> Option<Intersection>;
+    fn intersect(&self, ray: &Ray) -> Option<Intersection<'_>>;
+    // Whether anything lies within the ray's interval, need not find the closest hit
+    fn occludes(&self, ray: &Ray) -> bool {
+        self.intersect(ray).is_some()
+    }
+    fn bounds(&self) -> Option<Aabb>; // None for unbounded primitives
 }
 
 impl Shadable for Sphere {
@@ -174,30 +311,35 @@ impl Shadable for Sphere {
         Some(p.sub(&self.origin).norm())
     }
 
-    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
-        // TODO refactor
+    fn intersect(&self, ray: &Ray) -> Option<Intersection<'_>> {
         let oc = ray.origin().sub(&self.origin);
         let a = ray.direction().square();
         let b = ray.direction().dot(&oc) * -2.;
         let c = oc.dot(&oc) - (self.radius * self.radius);
 
         let det = b.mul_add(b, -(4. * a * c));
-        if det > 0. {
-            let inv = (2. * a).recip();
-            let tt = (b - det.sqrt()) * inv;
-            let t = (b + det.sqrt()) * inv;
-            let min = t.min(tt);
-            if min > 0.0 {
-                return Some(Intersection::new(
-                    self.normal(&ray.at_t(min)).unwrap(),
-                    &self.material,
-                    ray.clone_with_t(min),
-                ));
-            }
+        if det <= 0. {
+            return None;
         }
-        None
+        let inv = (2. * a).recip();
+        let near = (b - det.sqrt()) * inv;
+        let far = (b + det.sqrt()) * inv;
+        // closest root inside the ray's interval
+        let t = [near, far].iter().copied().find(|t| ray.in_range(*t))?;
+
+        // project back onto the sphere, that leaves only a few roundings of error
+        let to_hit = ray.at_t(t).sub(&self.origin);
+        let local = to_hit.scale(self.radius / to_hit.mag());
+        let p = self.origin.add(&local);
+        let p_error = local.abs().add(&self.origin.abs()).scale(gamma(5));
+        let hit = Intersection::new(local.scale(1. / self.radius), &self.material, ray.clone(), t);
+        Some(hit.with_point(p, p_error))
     }
 
+    fn bounds(&self) -> Option<Aabb> {
+        let r = Vec3::new(self.radius, self.radius, self.radius);
+        Some(Aabb::new(&self.origin.add(&r.scale(-1.)), &self.origin.add(&r)))
+    }
 }
 
 impl Shadable for Plane {
@@ -205,35 +347,68 @@ impl Shadable for Plane {
         Some(self.normal)
     }
 
-    fn intersect(&self, ray: &Ray) -> Option<Intersection> {
+    fn intersect(&self, ray: &Ray) -> Option<Intersection<'_>> {
         let ray_normal = ray.direction.dot(&self.normal);
 
         if ray_normal.abs() > 0.001f64 {
             let t = self.origin.sub(&ray.origin).dot(&self.normal) / ray_normal;
-            if t > 0.001f64 {
-                return Some(Intersection::new(self.normal(&ray.at_t(0.)).unwrap(),
-                                              &self.material,
-                                              ray.clone_with_t(t),
-                                              ));
+            if ray.in_range(t) {
+                // project back onto the plane
+                let p = ray.at_t(t);
+                let n = self.normal;
+                let p = p.add(&n.scale(-p.sub(&self.origin).dot(&n) / n.square()));
+                let p_error = p.abs().add(&self.origin.abs()).scale(gamma(7));
+                let hit = Intersection::new(self.normal, &self.material, ray.clone(), t);
+                return Some(hit.with_point(p, p_error));
             }
         }
         None
     }
+
+    fn bounds(&self) -> Option<Aabb> {
+        None
+    }
 }
 
-pub trait LightSource: std::fmt::Debug {
-    fn trace_light(&self, p: &Point3) -> Ray;
+// A point sampled on a light as seen from the point being shaded
+#[derive(Debug)]
+pub struct LightSample {
+    pub ray: Ray,        // shadow ray toward the sampled point
+    pub dist: f64,       // parametric distance to the sampled point, infinite for directional lights
+    pub radiance: Color, // arriving along the ray, point lights include the falloff
+    pub pdf: f64,        // solid angle density of the direction, 1 for point lights
+}
+
+pub trait LightSource: std::fmt::Debug + Send + Sync {
+    // `u` is uniform in [0,1)^2 and picks the point on the light
+    fn trace_light(&self, p: &Point3, u: (f64, f64)) -> LightSample;
+    // Shadow rays to average per shading point
+    fn samples(&self) -> u32 {
+        1
+    }
+    // Distance and radiance where a ray hits the emitting surface, never for point lights
+    fn intersect(&self, _ray: &Ray) -> Option<(f64, Color)> {
+        None
+    }
 }
 
 impl LightSource for PointLight {
-    fn trace_light(&self, p: &Point3) -> Ray {
-        let vec = self.origin.sub(&p);
+    fn trace_light(&self, p: &Point3, _u: (f64, f64)) -> LightSample {
+        let vec = self.origin.sub(p);
         let t = vec.mag();
-        Ray::new(p.clone(), vec.norm(), t)
+        LightSample {
+            ray: Ray::shadow(*p, vec.norm(), t),
+            dist: t,
+            radiance: self.col.mult(self.brightness / (t * t)),
+            pdf: 1.,
+        }
     }
 }
 
 pub mod camera;
+pub mod instance;
+pub mod light;
+pub mod mesh;
 pub mod material;
 
 #[cfg(test)]
@@ -243,15 +418,71 @@ mod test {
     use crate::approx::RelativeEq;
 
     use std::f64;
-    use crate::predef::materials::WATER;
+    use crate::predef::colors;
+    use crate::predef::materials::{GLASS, WATER};
+
+    #[test]
+    fn test_sphere_interval() {
+        let sphere = Sphere::new(Point3::new(0.,0.,-5.),1.,GLASS);
+        let mut ray = Ray::new(Point3::new(0.,0.,0.),Vec3::new(0.,0.,-1.));
+        assert_relative_eq!(sphere.intersect(&ray).unwrap().t, 4.);
+        // the near side is skipped once it is outside the interval
+        ray.t_min = 4.5;
+        assert_relative_eq!(sphere.intersect(&ray).unwrap().t, 6.);
+        ray.t_max = 5.5;
+        assert!(sphere.intersect(&ray).is_none());
+    }
+
+    #[test]
+    fn test_sphere_hit_from_inside() {
+        let sphere = Sphere::new(Point3::new(0.,0.,-5.),2.,GLASS);
+        let outside = sphere.intersect(&Ray::new(Point3::new(0.,0.,0.),Vec3::new(0.,0.,-1.))).unwrap();
+        assert!(outside.front_face);
+        assert_relative_eq!(outside.facing_normal().axis(2), 1.);
+
+        let inside = sphere.intersect(&Ray::new(Point3::new(0.,0.,-5.),Vec3::new(0.,0.,-1.))).unwrap();
+        assert_relative_eq!(inside.t, 2.);
+        assert!(!inside.front_face);
+        assert_relative_eq!(*inside.geometric_normal.z(), -1.);
+        assert_relative_eq!(inside.facing_normal().axis(2), 1.);
+
+        // a ray refracted into the sphere finds the exit on the far side
+        let refr_ray = outside.refract().unwrap();
+        let exit = sphere.intersect(&refr_ray).unwrap();
+        assert!(!exit.front_face);
+        assert_relative_eq!(exit.point().axis(2), -7., epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_spawned_rays_leave_the_surface() {
+        use crate::sampler::{self, Sampler};
+        // far from the origin the hit points carry a lot of rounding error
+        let center = Point3::new(1e4,-2e4,5e3);
+        let sphere = Sphere::new(center,100.,GLASS);
+        let plane = Plane::new(Point3::new(0.,-3e4,0.),Vec3::new(0.,1.,0.),GLASS);
+        let mut sampler = Sampler::new(11);
+        for _ in 0..1000 {
+            let dir = sampler::to_world(&sampler::cosine_hemisphere(sampler.next_2d()),&Vec3::new(-1.,2.,-0.5).norm());
+            let ray = Ray::new(center.add(&dir.scale(300.)),dir.scale(-1.));
+            let hit = sphere.intersect(&ray).unwrap();
+            assert!(sphere.intersect(&hit.reflect()).is_none());
+            // going in, the next hit is the far side of the sphere
+            assert!(sphere.intersect(&hit.spawn_ray(ray.direction)).unwrap().t > 1.);
+
+            let ray = Ray::new(Point3::new(5e3,-1e4,0.),dir.scale(-1.));
+            if let Some(hit) = plane.intersect(&ray) {
+                assert!(plane.intersect(&hit.reflect()).is_none());
+            }
+        }
+    }
 
     #[test]
     fn test_refr_ray_mag() {
         let normal = Vec3::new(0.,1.,0.);
         let ray_origin = Point3::new(-0.5,3_f64.sqrt()/2.,0.);
         let origin = Point3::new(0.,0.,0.);
-        let ray = Ray::new(ray_origin,origin.sub(&ray_origin),1.); // form a ray with 30 deg offset from normal
-        let hit = Intersection::new(Vec3::new(0.,1.,0.),&WATER,ray);
+        let ray = Ray::new(ray_origin,origin.sub(&ray_origin)); // form a ray with 30 deg offset from normal
+        let hit = Intersection::new(normal,&WATER,ray,1.);
         let refr_ray = hit.refract().unwrap();
         assert_relative_eq!(1.,refr_ray.direction.mag());
     }
@@ -261,11 +492,98 @@ mod test {
         let normal = Vec3::new(0.,1.,0.);
         let ray_origin = Point3::new(-0.5,3_f64.sqrt()/2.,0.);
         let origin = Point3::new(0.,0.,0.);
-        let ray = Ray::new(ray_origin,origin.sub(&ray_origin),1.); // form a ray with 30 deg offset from normal
-        let hit = Intersection::new(normal,&WATER,ray);
+        let ray = Ray::new(ray_origin,origin.sub(&ray_origin)); // form a ray with 30 deg offset from normal
+        let hit = Intersection::new(normal,&WATER,ray,1.);
         let refr_ray = hit.refract().unwrap();
         println!("refr_ray: {:?}",refr_ray.direction);
-        assert_relative_eq!(refr_ray.direction.dot(&hit.normal.scale(-1.)).acos() * 180./f64::consts::PI, 0.375_f64.asin() * 180./f64::consts::PI)
+        assert_relative_eq!(refr_ray.direction.dot(&hit.shading_normal.scale(-1.)).acos() * 180./f64::consts::PI, 0.375_f64.asin() * 180./f64::consts::PI)
+    }
+
+    #[test]
+    fn test_refract_exit() {
+        // leaving the water at 30 degrees bends away from the normal
+        let normal = Vec3::new(0.,1.,0.);
+        let dir = Vec3::new(0.5,3_f64.sqrt()/2.,0.);
+        let ray = Ray::new(dir.scale(-1.).as_point3(),dir);
+        let hit = Intersection::new(normal,&WATER,ray,1.);
+        let refr_ray = hit.refract().unwrap();
+        let sin_t = (4./3.) * 0.5;
+        assert_relative_eq!(*refr_ray.direction.x(), sin_t, epsilon = 1e-9);
+        assert_relative_eq!(*refr_ray.direction.y(), (1. - sin_t * sin_t).sqrt(), epsilon = 1e-9);
+        assert!(*refr_ray.origin.y() > 0.);
+    }
+
+    #[test]
+    fn test_fresnel() {
+        let normal = Vec3::new(0.,1.,0.);
+        let straight_down = Ray::new(Point3::new(0.,1.,0.),Vec3::new(0.,-1.,0.));
+        let hit = Intersection::new(normal,&GLASS,straight_down,1.);
+        assert_relative_eq!(hit.fresnel(), 0.04, epsilon = 1e-9);
+
+        // past the critical angle of water everything is reflected
+        let grazing = Vec3::new(0.9,0.1,0.).norm();
+        let hit = Intersection::new(normal,&WATER,Ray::new(grazing.scale(-1.).as_point3(),grazing),1.);
+        assert!(hit.refract().is_none());
+        assert_eq!(hit.fresnel(), 1.);
     }
 
+    #[test]
+    fn test_nested_media() {
+        let ice = Material::refractive(colors::WHITE,colors::WHITE,1.31).with_priority(1);
+        let air_bubble = Material::refractive(colors::WHITE,colors::WHITE,1.).with_priority(1);
+        let up = Vec3::new(0.,1.,0.);
+        let down = Vec3::new(0.,-1.,0.);
+        let ray_in = |media: &[Medium], dir: Vec3| {
+            let mut ray = Ray::new(dir.scale(-1.).as_point3(),dir);
+            media.iter().for_each(|m| ray.media.push(*m));
+            ray
+        };
+
+        // from water into ice, the fresnel term only sees the small jump in index
+        let water = WATER.medium().unwrap();
+        let hit = Intersection::new(up,&ice,ray_in(&[water],down),1.);
+        let expected = ((4./3. - 1.31_f64) / (4./3. + 1.31)).powi(2);
+        assert_relative_eq!(hit.fresnel(), expected, epsilon = 1e-12);
+        assert_eq!(hit.refract().unwrap().media[..], [water, ice.medium().unwrap()]);
+
+        // the water surface inside the ice is ignored
+        let hit = Intersection::new(down,&WATER,ray_in(&[water, ice.medium().unwrap()],down),1.);
+        assert!(hit.is_false_hit());
+        assert_eq!(hit.pass_through().media[..], [ice.medium().unwrap()]);
+
+        // a bubble in glass goes from 1.5 down to 1
+        let glass = GLASS.medium().unwrap();
+        let hit = Intersection::new(up,&air_bubble,ray_in(&[glass],down),1.);
+        assert!(!hit.is_false_hit());
+        assert_relative_eq!(hit.fresnel(), 0.04, epsilon = 1e-12);
+        let grazing = Vec3::new(0.9,-0.1,0.).norm();
+        assert!(Intersection::new(up,&air_bubble,ray_in(&[glass],grazing),1.).refract().is_none());
+    }
+
+    #[test]
+    fn test_deeply_nested_media() {
+        // five nested shells, each denser than the one around it
+        let shells: Vec<Material> = (0..5)
+            .map(|i| Material::refractive(colors::WHITE,colors::WHITE,1.1 + 0.1 * f64::from(i)))
+            .collect();
+        let media: Vec<Medium> = shells.iter().map(|m| m.medium().unwrap()).collect();
+        let up = Vec3::new(0.,1.,0.);
+        let down = Vec3::new(0.,-1.,0.);
+        let ray_in = |media: &[Medium], dir: Vec3| {
+            let mut ray = Ray::new(dir.scale(-1.).as_point3(),dir);
+            media.iter().for_each(|m| ray.media.push(*m));
+            ray
+        };
+
+        // entering the innermost shell from inside the other four
+        let hit = Intersection::new(up,&shells[4],ray_in(&media[..4],down),1.);
+        assert!(!hit.is_false_hit());
+        let inside = hit.refract().unwrap();
+        assert_eq!(inside.media[..], media[..]);
+
+        // and leaving it again back into the fourth
+        let hit = Intersection::new(up,&shells[4],ray_in(&inside.media,up),1.);
+        assert!(!hit.is_false_hit());
+        assert_eq!(hit.refract().unwrap().media[..], media[..4]);
+    }
 }
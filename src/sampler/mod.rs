@@ -0,0 +1,107 @@
+use std::f64::consts::PI;
+
+use crate::math::vec3::Vec3;
+
+// PCG32 random number generator (pcg-random.org), small and good enough for Monte Carlo
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    state: u64,
+    inc: u64,
+}
+
+impl Sampler {
+    pub fn new(seed: u64) -> Sampler {
+        let mut sampler = Sampler {
+            state: 0,
+            inc: (seed << 1) | 1,
+        };
+        sampler.next_u32();
+        sampler.state = sampler.state.wrapping_add(0x853c_49e6_748f_ea9b);
+        sampler.next_u32();
+        sampler
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(self.inc);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    // Uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64 {
+        f64::from(self.next_u32()) / 4_294_967_296.
+    }
+
+    pub fn next_2d(&mut self) -> (f64, f64) {
+        (self.next_f64(), self.next_f64())
+    }
+}
+
+// Shirley-Chiu concentric mapping of the unit square onto the unit disk
+pub fn concentric_disk(u: (f64, f64)) -> (f64, f64) {
+    let (x, y) = (2. * u.0 - 1., 2. * u.1 - 1.);
+    if x == 0. && y == 0. {
+        return (0., 0.);
+    }
+    let (r, theta) = if x.abs() > y.abs() {
+        (x, PI / 4. * (y / x))
+    } else {
+        (y, PI / 2. - PI / 4. * (x / y))
+    };
+    (r * theta.cos(), r * theta.sin())
+}
+
+// Cosine weighted direction around +z, pdf = cos(theta) / pi
+pub fn cosine_hemisphere(u: (f64, f64)) -> Vec3 {
+    let (x, y) = concentric_disk(u);
+    let z = (1. - x * x - y * y).max(0.).sqrt();
+    Vec3::new(x, y, z)
+}
+
+// Uniform direction within angle acos(cos_max) of +z, density 1 / (2 pi (1 - cos_max))
+pub fn uniform_cone(u: (f64, f64), cos_max: f64) -> Vec3 {
+    let cos_theta = 1. - u.0 * (1. - cos_max);
+    let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+    let phi = 2. * PI * u.1;
+    Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+// Rotates a +z based local direction into the frame around n
+pub fn to_world(local: &Vec3, n: &Vec3) -> Vec3 {
+    let (t, b) = n.orthonormal_basis();
+    t.scale(*local.x())
+        .add(&b.scale(*local.y()))
+        .add(&n.scale(*local.z()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_uniform_range() {
+        let mut sampler = Sampler::new(42);
+        let mut sum = 0.;
+        for _ in 0..10000 {
+            let u = sampler.next_f64();
+            assert!((0. ..1.).contains(&u));
+            sum += u;
+        }
+        assert!((sum / 10000. - 0.5).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_cosine_hemisphere() {
+        let mut sampler = Sampler::new(7);
+        let n = Vec3::new(1., 1., 0.).norm();
+        for _ in 0..1000 {
+            let dir = to_world(&cosine_hemisphere(sampler.next_2d()), &n);
+            assert!(dir.dot(&n) >= 0.);
+            assert!((dir.mag() - 1.).abs() < 1e-9);
+        }
+    }
+}
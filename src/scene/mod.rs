@@ -1,20 +1,70 @@
 extern crate image;
 use image::{ImageBuffer, Rgb};
 
+use crate::accel::bvh::Bvh;
+use crate::film::filter::Filter;
+use crate::film::{Film, ToneMap};
 use crate::math::ray::Ray;
-use crate::primitives::camera::Camera;
+use crate::primitives::camera::{Camera, PerspectiveCamera};
 use crate::primitives::material::Color;
-use crate::primitives::material::Material;
 use crate::primitives::Intersection;
 use crate::primitives::LightSource;
 use crate::primitives::Shadable;
+use crate::integrator::Integrator;
+use crate::sampler::Sampler;
 
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+#[derive(Debug, Copy, Clone)]
+pub struct RenderSettings {
+    pub samples_per_pixel: u32,
+    pub filter: Filter,
+    pub tone_map: ToneMap,
+    pub threads: usize, // 0 uses every available core
+    pub tile_size: u32,
+    pub crop: Option<Crop>, // render only this part of the image
+}
+
+// Pixels [x0, x1) x [y0, y1) of the image
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Crop {
+    pub x0: u32,
+    pub y0: u32,
+    pub x1: u32,
+    pub y1: u32,
+}
+
+impl RenderSettings {
+    pub fn thread_count(&self) -> usize {
+        if self.threads > 0 {
+            return self.threads;
+        }
+        thread::available_parallelism().map_or(1, |n| n.get())
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            samples_per_pixel: 1,
+            filter: Filter::default(),
+            tone_map: ToneMap::default(),
+            threads: 0,
+            tile_size: 16,
+            crop: None,
+        }
+    }
+}
 
 pub struct Scene<'a> {
-    camera: Camera,
-    primitives: Vec<Box<dyn Shadable + 'a>>,
+    camera: Box<dyn Camera + 'a>,
+    primitives: Vec<Box<dyn Shadable + 'a>>, // bounded, stored in the bvh
+    unbounded: Vec<Box<dyn Shadable + 'a>>,  // planes etc. that are always tested
+    bvh: Option<Bvh>,
     lights: Vec<Box<dyn LightSource + 'a>>,
     background_col: Color,
+    settings: RenderSettings,
 }
 
 impl<'a> Scene<'a> {
@@ -22,16 +72,44 @@ impl<'a> Scene<'a> {
         Default::default()
     }
 
-    pub fn set_camera(&mut self, c: Camera) {
-        self.camera = c;
+    pub fn set_camera<C: Camera + 'a>(&mut self, c: C) {
+        self.camera = Box::new(c);
+    }
+
+    pub fn camera(&self) -> &dyn Camera {
+        self.camera.as_ref()
+    }
+
+    pub fn camera_mut(&mut self) -> &mut dyn Camera {
+        self.camera.as_mut()
     }
 
     pub fn set_background_col(&mut self, col: Color) {
         self.background_col = col;
     }
 
+    pub fn background_col(&self) -> &Color {
+        &self.background_col
+    }
+
+    pub fn lights(&self) -> &[Box<dyn LightSource + 'a>] {
+        &self.lights
+    }
+
+    pub fn settings(&self) -> &RenderSettings {
+        &self.settings
+    }
+
+    pub fn settings_mut(&mut self) -> &mut RenderSettings {
+        &mut self.settings
+    }
+
     pub fn add_object<T: Shadable + 'a>(&mut self, obj: T) {
-        self.primitives.push(Box::new(obj));
+        match obj.bounds() {
+            Some(_) => self.primitives.push(Box::new(obj)),
+            None => self.unbounded.push(Box::new(obj)),
+        }
+        self.bvh = None;
     }
 
     pub fn add_light<L: LightSource + 'a>(&mut self, light: L) {
@@ -39,114 +117,257 @@ impl<'a> Scene<'a> {
     }
 
     pub fn print_objects(&self) {
-        for object in self.primitives.iter() {
+        for object in self.primitives.iter().chain(self.unbounded.iter()) {
             print!("{:?}", object);
         }
         println!();
     }
 
-    pub fn find_nearest_intersect(&self, ray: &Ray) -> Option<Intersection> {
-        let mut current_hit = None;
-        for obj in self.primitives.iter() {
-            // find nearest hit
-            if let Some(intersection) = obj.intersect(&ray) {
-                match current_hit {
-                    Some(hit) => {
-                        if intersection < hit {
-                            current_hit = Some(intersection);
-                        } else {
-                            current_hit = Some(hit);
-                        }
-                    }
-                    None => current_hit = Some(intersection),
-                }
+    // Builds the acceleration structure, called by render() if the scene changed
+    pub fn build(&mut self) {
+        let bounds: Vec<_> = self
+            .primitives
+            .iter()
+            .map(|obj| obj.bounds().unwrap())
+            .collect();
+        self.bvh = Some(Bvh::build(&bounds));
+    }
+
+    pub fn find_nearest_intersect(&self, ray: &Ray) -> Option<Intersection<'_>> {
+        // every hit ends the ray there, so later tests only look in front of it
+        let mut ray = ray.clone();
+        let nearest = match &self.bvh {
+            Some(bvh) => bvh.intersect(&ray, |i, ray| self.primitives[i].intersect(ray)),
+            None => Self::nearest_in(&self.primitives, &mut ray, None),
+        };
+        if let Some(hit) = &nearest {
+            ray.t_max = hit.t;
+        }
+        Self::nearest_in(&self.unbounded, &mut ray, nearest)
+    }
+
+    fn nearest_in<'s>(
+        objects: &'s [Box<dyn Shadable + 'a>],
+        ray: &mut Ray,
+        mut nearest: Option<Intersection<'s>>,
+    ) -> Option<Intersection<'s>> {
+        for obj in objects.iter() {
+            if let Some(hit) = obj.intersect(ray) {
+                ray.t_max = hit.t;
+                nearest = Some(hit);
             }
         }
-        if let Some(mut intersection) = current_hit {
-            intersection.add_bias();
-            current_hit = Some(intersection);
+        nearest
+    }
+
+    // Radiance of the nearest area light along the ray if it is in front of `hit`
+    pub fn emitter_before(&self, ray: &Ray, hit: Option<&Intersection>) -> Option<Color> {
+        let mut ray = ray.clone();
+        if let Some(hit) = hit {
+            ray.t_max = hit.t;
         }
-        current_hit
+        self.lights
+            .iter()
+            .filter_map(|light| light.intersect(&ray))
+            .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(_, radiance)| radiance)
     }
 
+    // Whether anything blocks the ray between its t_min and t_max
     pub fn is_occluded(&self, ray: &Ray) -> bool {
-        for obj in self.primitives.iter() {
-            if let Some(_intersection) = obj.intersect(&ray) {
-                return true;
-            }
-        }
-        false
+        let bounded = match &self.bvh {
+            Some(bvh) => bvh.any_hit(ray, |i| self.primitives[i].occludes(ray)),
+            None => self.primitives.iter().any(|obj| obj.occludes(ray)),
+        };
+        bounded || self.unbounded.iter().any(|obj| obj.occludes(ray))
     }
 
-    pub fn shade(&self, hit: Intersection, depth: u32, max_depth: u32) -> Color {
-        let mut col = self.background_col;
-        if depth == max_depth {
-            //println!("Max depth exceeded");
-            return col;
+    // Renders unclamped radiance, see render() for a displayable image
+    pub fn render_film(&mut self, integrator: &dyn Integrator) -> Film {
+        if self.bvh.is_none() {
+            self.build();
         }
-        let hit_point = hit.point();
-
-        match hit.mat() {
-            Material::Diffuse { diff_col }  => {
-                for light in self.lights.iter() {
-                    let shadow_ray = light.trace_light(&hit_point);
-                    if !self.is_occluded(&shadow_ray) {
-                        let cos = shadow_ray.direction().dot(&hit.normal).abs();
-                        col.add(&diff_col.mult(cos));
-                        col = col.clamp(); // TODO add tone mapping to remove this
-                    }
-                }
-                col
-            },
-
-            Material::Specular { spec_col } => {
-                let refl_ray = &hit.reflect();
-                let new_hit = self.find_nearest_intersect(&refl_ray);
-                match new_hit {
-                    Some(intersection) => self.shade(intersection,depth+1,max_depth).mix(&spec_col,0.9),
-                    None => col,
-                }
-            },
-
-            Material::Refractive { spec_col, refr_col, ior } => {
-                let refr_ray = &hit.refract();
-                let new_hit = match refr_ray {
-                    Some(ray) => self.find_nearest_intersect(&ray),
-                    None => None
-                };
-                match new_hit {
-                    Some(intersection) => self.shade(intersection,depth+1,max_depth),
-                    None => col,
-                }
-            },
+        // samples just outside the window still reach its edge pixels
+        let window = self.window();
+        let (res_x, res_y) = self.camera.resolution();
+        let margin = self.filter_margin();
+        let sampled = Crop {
+            x0: window.x0.saturating_sub(margin),
+            y0: window.y0.saturating_sub(margin),
+            x1: (window.x1 + margin).min(res_x),
+            y1: (window.y1 + margin).min(res_y),
+        };
+        let tiles = tiles(&sampled, self.settings.tile_size.max(1));
+        let threads = self.settings.thread_count().min(tiles.len()).max(1);
+
+        // workers pull the next tile until none are left
+        let next_tile = AtomicUsize::new(0);
+        let scene: &Scene = self;
+        let mut rendered: Vec<(usize, Film)> = thread::scope(|s| {
+            let workers: Vec<_> = (0..threads)
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut done = Vec::new();
+                        loop {
+                            let i = next_tile.fetch_add(1, Ordering::Relaxed);
+                            if i >= tiles.len() {
+                                break done;
+                            }
+                            done.push((i, scene.render_tile(&tiles[i], &window, integrator)));
+                        }
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("Render thread panicked"))
+                .collect()
+        });
+
+        // merge in tile order so the sums don't depend on scheduling
+        rendered.sort_by_key(|(i, _)| *i);
+        let (width, height) = (window.x1 - window.x0, window.y1 - window.y0);
+        let mut film = Film::with_window(window.x0, window.y0, width, height);
+        for (_, tile) in rendered.iter() {
+            film.merge(tile);
+        }
+        film
+    }
 
-            _ => panic!("Material Variant not implemented!"),
+    // Part of the image being rendered, the crop clipped to the resolution
+    fn window(&self) -> Crop {
+        let (res_x, res_y) = self.camera.resolution();
+        let full = Crop {
+            x0: 0,
+            y0: 0,
+            x1: res_x,
+            y1: res_y,
+        };
+        let crop = self.settings.crop.unwrap_or(full);
+        let x1 = crop.x1.min(res_x);
+        let y1 = crop.y1.min(res_y);
+        Crop {
+            x0: crop.x0.min(x1),
+            y0: crop.y0.min(y1),
+            x1,
+            y1,
         }
     }
 
-    pub fn render(&mut self) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
-        let mut buff = self.camera.new_buffer();
+    // Pixels the filter reaches around the one a sample falls in
+    fn filter_margin(&self) -> u32 {
+        (self.settings.filter.radius() - 0.5).ceil().max(0.) as u32
+    }
 
-        for (x, y, pixel) in buff.enumerate_pixels_mut() {
-            let ray = self.camera.generate_ray(x, y);
-            let hit = self.find_nearest_intersect(&ray);
+    // Samples every pixel of the tile, the film covers the pixels of the window the filter reaches
+    fn render_tile(&self, tile: &Crop, window: &Crop, integrator: &dyn Integrator) -> Film {
+        let (res_x, _) = self.camera.resolution();
+        let filter = &self.settings.filter;
+        let margin = self.filter_margin();
+        let x0 = tile.x0.saturating_sub(margin).max(window.x0);
+        let y0 = tile.y0.saturating_sub(margin).max(window.y0);
+        let x1 = (tile.x1 + margin).min(window.x1);
+        let y1 = (tile.y1 + margin).min(window.y1);
+        let mut film = Film::with_window(x0, y0, x1.saturating_sub(x0), y1.saturating_sub(y0));
 
-            *pixel = match hit {
-                Some(intersection) => self.shade(intersection,0,64).to_rgb(),
-                None => self.background_col.to_rgb(),
-            };
+        let samples = self.settings.samples_per_pixel.max(1);
+        for y in tile.y0..tile.y1 {
+            for x in tile.x0..tile.x1 {
+                // seeded per pixel so renders are repeatable
+                let mut sampler = Sampler::new(u64::from(y) * u64::from(res_x) + u64::from(x));
+                for i in 0..samples {
+                    let (dx, dy) = pixel_offset(i, samples, &mut sampler);
+                    let (fx, fy) = (f64::from(x) + dx, f64::from(y) + dy);
+                    // rays the projection can't produce still count as black
+                    let col = match self.camera.generate_ray(fx, fy, sampler.next_2d()) {
+                        Some(ray) => integrator.li(self, &ray, &mut sampler),
+                        None => Color::new(0., 0., 0.),
+                    };
+                    film.splat(fx, fy, &col, filter);
+                }
+            }
         }
-        buff
+        film
     }
+
+    pub fn render(&mut self, integrator: &dyn Integrator) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        self.render_film(integrator).develop(&self.settings.tone_map)
+    }
+}
+
+// Splits the window into rows of tiles that one worker renders at a time,
+// edge tiles are smaller
+fn tiles(window: &Crop, size: u32) -> Vec<Crop> {
+    let mut tiles = Vec::new();
+    for y0 in (window.y0..window.y1).step_by(size as usize) {
+        for x0 in (window.x0..window.x1).step_by(size as usize) {
+            tiles.push(Crop {
+                x0,
+                y0,
+                x1: (x0 + size).min(window.x1),
+                y1: (y0 + size).min(window.y1),
+            });
+        }
+    }
+    tiles
+}
+
+// Jittered sub-pixel position of sample i, stratified over a grid of
+// strata_x * strata_y cells that covers the pixel exactly once per sample count
+fn pixel_offset(i: u32, samples: u32, sampler: &mut Sampler) -> (f64, f64) {
+    if samples == 1 {
+        return (0.5, 0.5);
+    }
+    // the largest divisor not above the square root keeps the cells close to square
+    let strata_y = (1..=f64::from(samples).sqrt() as u32)
+        .rev()
+        .find(|d| samples.is_multiple_of(*d))
+        .unwrap_or(1);
+    let strata_x = samples / strata_y;
+    let (u, v) = sampler.next_2d();
+    let cell_x = f64::from(i % strata_x);
+    let cell_y = f64::from(i / strata_x);
+    (
+        (cell_x + u) / f64::from(strata_x),
+        (cell_y + v) / f64::from(strata_y),
+    )
 }
 
 impl<'a> Default for Scene<'a> {
     fn default() -> Scene<'a> {
         Scene {
-            camera: Camera::default(),
+            camera: Box::new(PerspectiveCamera::default()),
             primitives: Vec::new(),
+            unbounded: Vec::new(),
+            bvh: None,
             lights: Vec::new(),
             background_col: Color::new(0.1, 0.1, 0.1),
+            settings: RenderSettings::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pixel_offsets_are_centered() {
+        for samples in [2, 3, 4, 6, 7].iter() {
+            let (mut sum_x, mut sum_y) = (0., 0.);
+            let pixels = 2000;
+            for seed in 0..pixels {
+                let mut sampler = Sampler::new(seed);
+                for i in 0..*samples {
+                    let (dx, dy) = pixel_offset(i, *samples, &mut sampler);
+                    assert!((0. ..1.).contains(&dx) && (0. ..1.).contains(&dy));
+                    sum_x += dx;
+                    sum_y += dy;
+                }
+            }
+            let n = (pixels * u64::from(*samples)) as f64;
+            assert!((sum_x / n - 0.5).abs() < 0.01, "{} samples, mean x {}", samples, sum_x / n);
+            assert!((sum_y / n - 0.5).abs() < 0.01, "{} samples, mean y {}", samples, sum_y / n);
         }
     }
 }
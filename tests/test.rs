@@ -1,6 +1,177 @@
-use ton;
+use ton::film::filter::Filter;
+use ton::integrator::{DepthIntegrator, Integrator, NormalIntegrator, PathIntegrator, WhittedIntegrator};
+use ton::math::point3::Point3;
+use ton::math::vec3::Vec3;
+use ton::predef::colors;
+use ton::predef::materials::GLASS;
+use ton::primitives::camera::PerspectiveCamera;
+use ton::primitives::material::{Color, Material};
+use ton::primitives::light::DiskLight;
+use ton::primitives::{Plane, PointLight, Sphere};
+use ton::scene::{Crop, Scene};
 
 #[test]
 fn it_lives() {
     assert_eq!(4,2+2);
 }
+
+fn small_scene<'a>() -> Scene<'a> {
+    let mut scene = Scene::new();
+    let mut camera = PerspectiveCamera::default();
+    camera.set_resolution(8, 6);
+    scene.set_camera(camera);
+    scene.add_object(Sphere::new(Point3::new(0., 0., -3.), 1., GLASS));
+    scene
+}
+
+#[test]
+fn integrators_are_interchangeable() {
+    let mut scene = small_scene();
+    let integrators: Vec<Box<dyn Integrator>> = vec![
+        Box::new(WhittedIntegrator::default()),
+        Box::new(NormalIntegrator),
+        Box::new(DepthIntegrator::new(4.)),
+    ];
+    for integrator in integrators.iter() {
+        let image = scene.render(integrator.as_ref());
+        assert_eq!(image.dimensions(), (8, 6));
+    }
+
+    // the sphere faces the camera in the middle of the image
+    let normals = scene.render(&NormalIntegrator);
+    let center = normals.get_pixel(4, 3);
+    assert!(center[2] > 200);
+    assert_eq!(normals.get_pixel(0, 0)[2], 0);
+}
+
+#[test]
+fn thread_count_does_not_change_the_image() {
+    let mut scene = small_scene();
+    let settings = scene.settings_mut();
+    settings.samples_per_pixel = 4;
+    settings.filter = Filter::Mitchell { radius: 2., b: 1. / 3., c: 1. / 3. };
+    settings.tile_size = 3;
+    settings.threads = 1;
+    let single = scene.render(&NormalIntegrator);
+    scene.settings_mut().threads = 4;
+    let multi = scene.render(&NormalIntegrator);
+    assert_eq!(single.into_raw(), multi.into_raw());
+}
+
+#[test]
+fn example_scene_loads() {
+    let scene = ton::loader::scene::load("scenes/spheres.scene").unwrap();
+    assert_eq!(scene.camera().resolution(), (640, 480));
+    assert_eq!(scene.lights().len(), 1);
+}
+
+#[test]
+fn crop_matches_the_full_render() {
+    let mut scene = small_scene();
+    scene.settings_mut().filter = Filter::Tent { radius: 1.5 };
+    let full = scene.render(&NormalIntegrator);
+    scene.settings_mut().crop = Some(Crop { x0: 2, y0: 1, x1: 7, y1: 4 });
+    let cropped = scene.render(&NormalIntegrator);
+    assert_eq!(cropped.dimensions(), (5, 3));
+    for (x, y, pixel) in cropped.enumerate_pixels() {
+        assert_eq!(pixel, full.get_pixel(x + 2, y + 1));
+    }
+}
+
+#[test]
+fn mixed_materials_render() {
+    let mut scene = Scene::new();
+    let mut camera = PerspectiveCamera::default();
+    camera.set_resolution(8, 6);
+    scene.set_camera(camera);
+    scene.set_background_col(colors::BLACK);
+    let plastic = Material::mixed(colors::RED, colors::WHITE, 0.3);
+    scene.add_object(Sphere::new(Point3::new(0., 0., -3.), 1., plastic));
+    scene.add_light(PointLight::new(Point3::new(0., 2., 0.), colors::WHITE, 20.));
+
+    let integrators: Vec<Box<dyn Integrator>> = vec![
+        Box::new(WhittedIntegrator::default()),
+        Box::new(PathIntegrator::default()),
+    ];
+    for integrator in integrators.iter() {
+        let image = scene.render(integrator.as_ref());
+        let center = image.get_pixel(4, 2);
+        assert!(center[0] > center[2], "{:?} should shade the sphere red", integrator);
+    }
+}
+
+// A grey wall 3 units in front of the camera, seen head on through a single pixel
+fn wall_scene<'a>(albedo: f64, background: Color) -> Scene<'a> {
+    let mut scene = Scene::new();
+    let mut camera = PerspectiveCamera::default();
+    camera.set_resolution(1, 1).set_fov(1.);
+    scene.set_camera(camera);
+    scene.set_background_col(background);
+    let wall = Material::diffuse(Color::new(albedo, albedo, albedo));
+    scene.add_object(Plane::new(Point3::new(0., 0., -3.), Vec3::new(0., 0., 1.), wall));
+    scene
+}
+
+#[test]
+fn point_lights_fall_off_with_distance() {
+    let albedo = 0.5;
+    let mut scene = wall_scene(albedo, colors::BLACK);
+    let mut light = PointLight::new(Point3::new(0., 0., 0.), colors::WHITE, 1.);
+    light.set_power(100.);
+    scene.add_light(light);
+
+    let expected = 100. / (4. * std::f64::consts::PI) * albedo / std::f64::consts::PI / 9.;
+    let integrators: Vec<Box<dyn Integrator>> = vec![
+        Box::new(WhittedIntegrator::default()),
+        Box::new(PathIntegrator::default()),
+    ];
+    for integrator in integrators.iter() {
+        let radiance = scene.render_film(integrator.as_ref()).pixel(0, 0).red;
+        assert!((radiance - expected).abs() < 0.01 * expected, "{:?} gave {}, expected {}", integrator, radiance, expected);
+    }
+}
+
+#[test]
+fn direct_light_ignores_the_background() {
+    // the wall hides the background, so halving the distance to the light must
+    // give exactly four times the radiance
+    let lit_from = |z: f64| {
+        let mut scene = wall_scene(0.5, Color::new(0.3, 0.3, 0.3));
+        scene.add_light(PointLight::new(Point3::new(0., 0., z), colors::WHITE, 10.));
+        scene.render_film(&WhittedIntegrator::default()).pixel(0, 0).red
+    };
+    let (far, near) = (lit_from(0.), lit_from(-1.5));
+    assert!((near / far - 4.).abs() < 1e-9, "ratio {}", near / far);
+}
+
+#[test]
+fn objects_behind_a_light_cast_no_shadow() {
+    let mut scene = wall_scene(0.5, colors::BLACK);
+    scene.add_light(PointLight::new(Point3::new(0., 0., -1.), colors::WHITE, 10.));
+    // behind the camera, on the far side of the light as seen from the wall
+    scene.add_object(Sphere::new(Point3::new(0., 0., 2.), 0.5, Material::diffuse(colors::WHITE)));
+
+    let radiance = scene.render_film(&WhittedIntegrator::default()).pixel(0, 0);
+    assert!(radiance.red > 0.);
+}
+
+#[test]
+fn whitted_and_path_agree_on_mixed_materials() {
+    // the wall mirrors a disk light behind the camera and is lit diffusely by it,
+    // with a black background neither integrator picks up any other light
+    let mut scene = Scene::new();
+    let mut camera = PerspectiveCamera::default();
+    camera.set_resolution(1, 1).set_fov(1.);
+    scene.set_camera(camera);
+    scene.set_background_col(colors::BLACK);
+    let plastic = Material::mixed(Color::new(0.6, 0.6, 0.6), Color::new(0.8, 0.4, 0.2), 0.3);
+    scene.add_object(Plane::new(Point3::new(0., 0., -3.), Vec3::new(0., 0., 1.), plastic));
+    scene.add_light(DiskLight::new(Point3::new(0., 0., 1.), Vec3::new(0., 0., -1.), 0.5, colors::WHITE, 2.));
+    scene.settings_mut().samples_per_pixel = 16384;
+
+    let whitted = scene.render_film(&WhittedIntegrator::default()).pixel(0, 0);
+    let path = scene.render_film(&PathIntegrator::default()).pixel(0, 0);
+    for (w, p) in [(whitted.red, path.red), (whitted.green, path.green), (whitted.blue, path.blue)].iter() {
+        assert!((w - p).abs() < 0.05 * w, "whitted {:?}, path {:?}", whitted, path);
+    }
+}